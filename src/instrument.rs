@@ -12,7 +12,7 @@ use spin_sleep;
 use crate::instructions::*;
 use crate::registers::{ChanMask, ChannelState, IOMask, OutputRange};
 use crate::registers::{ChannelConf, PulseAttrs, ClusterMask, ArbMask};
-use crate::registers::{IOEnable, IODir, AuxDACFn};
+use crate::registers::{IOEnable, IODir, AuxDACFn, SourceConf, CurrentSourceResistor};
 use crate::registers::consts::HSCLUSTERMAP;
 use crate::memory::{MemMan, Chunk, MemoryError};
 
@@ -30,6 +30,13 @@ const BLFLAGS_R: bl::Flags = bl::Flags::NoFlags;
 const INBUF: usize = 64*std::mem::size_of::<u32>();
 pub(crate) const VALUEAVAILFLAG: u32 = 0xcafebabe;
 const INSTRCAP: usize = 2048*9*std::mem::size_of::<u32>();
+// Minimum number of free memory blocks to keep during background acquisitions
+const ACQMINFREE: usize = 4096;
+// Settling time (ns) of the selector circuits after a configuration change
+const SELSETTLE: u128 = 10_000;
+// Distance from target (decades) above which a cell is coarse-programmed
+// before the rest of the matrix; this is a factor of 2
+const PROGCOARSE: f32 = 0.30103;
 
 // We are caching common instructions
 lazy_static! {
@@ -148,6 +155,21 @@ pub enum ArC2Error {
     HSDurationError(usize),
     #[error("Attempting to toggle a previously set HS polarity bit")]
     HSPolarityBitError(),
+    /// Waveform samples not in ascending time order
+    #[error("Waveform sample at {0} ns is not later than the previous sample")]
+    WaveformTimingError(u128),
+    /// Vector or matrix dimensions do not match the channels provided
+    #[error("Dimension mismatch: got {0} values, expected {1}")]
+    DimensionError(usize, usize),
+    /// MAC calibration could not be fitted or parsed
+    #[error("Calibration error: {0}")]
+    CalibrationError(String),
+    /// AUX DAC function cannot be used as an arbitrary supply
+    #[error("AUX DAC function {0} is not an arbitrary supply")]
+    AuxFunctionError(usize),
+    /// Channel does not exist
+    #[error("Invalid channel: {0}")]
+    InvalidChannel(usize),
     /// Unsupported platform
     #[error("Hardware functionality unavailable on this platform")]
     PlatformUnsupported(),
@@ -176,6 +198,78 @@ pub enum BiasOrder {
     Rows
 }
 
+/// Biasing scheme for unselected lines during array operations
+///
+/// This enum controls how the lines of a 32×32 array that are not involved
+/// in a read or pulse operation are biased. Operations with a `BiasScheme`
+/// regard the line of the low potential channel as the *selected line* and
+/// lines of the same type (rows or columns) as *unselected lines*. Likewise
+/// lines of the opposite type that are not part of the operation are
+/// *unselected opposite lines*. For a voltage `V` applied between the
+/// selected low and high lines, unselected lines are set at a fraction of
+/// `V` measured from the high potential end (see [`BiasScheme::levels`]).
+///
+/// * `Grounded`: All unselected lines are held at 0.0 V, irrespective of the
+///   voltages of the selected lines. This is the default behaviour of read
+///   operations.
+/// * `Floating`: All unselected lines are disconnected and left floating.
+/// * `VHalf`: All unselected lines are held at `V/2`. Maximum voltage across
+///   any unselected device is `V/2`.
+/// * `VThird`: Unselected lines are held at `V/3` and unselected opposite
+///   lines at `2V/3`. Maximum voltage across any unselected device is `V/3`.
+/// * `Custom(a, b)`: Unselected lines are held at `a·V` and unselected opposite
+///   lines at `b·V`.
+///
+/// This is typically used to mitigate half-select disturb and sneak path
+/// currents in passive crossbar arrays. See [`Instrument::read_slice_with_scheme`]
+/// and [`Instrument::pulse_one_with_scheme`] for more details.
+///
+/// Schemes are supported through the `*_with_scheme` variants of
+/// [`Instrument::read_slice`], [`Instrument::read_all`], [`Instrument::pulse_one`]
+/// and [`Instrument::pulse_slice`] instead of an extra argument to these, so
+/// that existing callers, including the Python bindings, keep working
+/// unchanged. The original read functions are equivalent to `Grounded` whereas
+/// the original pulse functions leave the unselected lines untouched.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BiasScheme {
+    Grounded,
+    Floating,
+    VHalf,
+    VThird,
+    Custom(f32, f32)
+}
+
+impl BiasScheme {
+    /// Voltages of the unselected lines and unselected opposite lines
+    /// respectively when the selected low line is at `vlow` and the selected
+    /// high line is at `vhigh`. This will return `None` for
+    /// [`BiasScheme::Floating`] as no voltage is applied in this case.
+    ///
+    /// ```
+    /// use libarc2::BiasScheme;
+    ///
+    /// // A read at 0.3 V; low line at -0.3 V, sense lines at 0.0 V
+    /// assert_eq!(BiasScheme::VHalf.levels(-0.3, 0.0), Some((-0.15, -0.15)));
+    /// // A differential 3.0 V pulse; low line at -1.5 V, high line at 1.5 V
+    /// assert_eq!(BiasScheme::VThird.levels(-1.5, 1.5), Some((0.5, -0.5)));
+    /// assert_eq!(BiasScheme::Grounded.levels(-1.5, 1.5), Some((0.0, 0.0)));
+    /// assert_eq!(BiasScheme::Floating.levels(-1.5, 1.5), None);
+    /// ```
+    pub fn levels(&self, vlow: f32, vhigh: f32) -> Option<(f32, f32)> {
+        let (a, b) = match self {
+            BiasScheme::Grounded => { return Some((0.0, 0.0)); },
+            BiasScheme::Floating => { return None; },
+            BiasScheme::VHalf => (0.5, 0.5),
+            BiasScheme::VThird => (1.0/3.0, 2.0/3.0),
+            BiasScheme::Custom(a, b) => (*a, *b)
+        };
+
+        let v = vlow - vhigh;
+
+        Some((vhigh + a*v, vhigh + b*v))
+    }
+}
+
 /// Daughterboard mode of operation
 ///
 /// This enum is used to communicate how devices are controlled when a
@@ -255,6 +349,160 @@ impl ReadAfter {
     }
 }
 
+/// Shape of a voltage ramp
+///
+/// This enum controls the voltage profile generated by
+/// [`Instrument::generate_ramp_shaped`]. `Linear` is the classic staircase
+/// from `vstart` up to, but not including, `vstop` as done by
+/// [`Instrument::generate_ramp`]. `Triangle` ramps from `vstart` to `vstop`
+/// and back to `vstart` whereas `Bipolar` does a full I–V loop going from
+/// `vstart` to `vstop`, back to `vstart`, then to `-vstop` and finally
+/// back to `vstart` (`vstart` would typically be 0.0 V). For both `Triangle`
+/// and `Bipolar` the turning points are always included, even if they are
+/// not an exact multiple of `vstep` away from `vstart`, and every down leg
+/// goes through exactly the same voltages as the corresponding up leg.
+/// `Logarithmic` steps from `vstart` up to, but not including, `vstop` with
+/// the specified number of steps per decade; `vstep` is ignored in that case
+/// and `vstart`, `vstop` must be non-zero and of the same polarity.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RampShape {
+    Linear,
+    Triangle,
+    Bipolar,
+    Logarithmic(usize)
+}
+
+impl RampShape {
+
+    /// Generate the list of voltages for `cycles` repetitions of this
+    /// ramp shape. For `Triangle` and `Bipolar` consecutive cycles share
+    /// their start and end voltage so it is only included once.
+    ///
+    /// ```
+    /// use libarc2::RampShape;
+    ///
+    /// let v = RampShape::Triangle.voltages(0.0, 0.5, 1.0, 1).unwrap();
+    /// assert_eq!(v, vec![0.0, 0.5, 1.0, 0.5, 0.0]);
+    ///
+    /// let v = RampShape::Bipolar.voltages(0.0, 0.5, 1.0, 2).unwrap();
+    /// assert_eq!(v, vec![0.0, 0.5, 1.0, 0.5, 0.0, -0.5, -1.0, -0.5, 0.0,
+    ///     0.5, 1.0, 0.5, 0.0, -0.5, -1.0, -0.5, 0.0]);
+    ///
+    /// // Down legs sample the same voltages as up legs
+    /// let v = RampShape::Triangle.voltages(0.0, 0.3, 1.0, 1).unwrap();
+    /// assert_eq!(v.len(), 9);
+    /// assert!(v.iter().zip(v.iter().rev()).all(|(a, b)| a == b));
+    ///
+    /// let v = RampShape::Logarithmic(1).voltages(0.01, 0.0, 10.0, 1).unwrap();
+    /// assert_eq!(v.len(), 3);
+    /// assert!((v[2] - 1.0).abs() < 1e-6);
+    ///
+    /// assert!(RampShape::Linear.voltages(0.0, 0.0, 1.0, 1).is_err());
+    /// ```
+    pub fn voltages(&self, vstart: f32, vstep: f32, vstop: f32, cycles: usize)
+        -> Result<Vec<f32>, ArC2Error> {
+
+        // Staircase from `from` to `to` including both ends
+        fn __leg(from: f32, to: f32, step: f32) -> Vec<f32> {
+            let step = if to >= from { step.abs() } else { -step.abs() };
+            let steps = f32::ceil((to - from)/step) as usize;
+            let mut res: Vec<f32> = (0..steps).map(|idx| from + step*(idx as f32)).collect();
+            res.push(to);
+            res
+        }
+
+        let cycle: Vec<f32> = match self {
+            RampShape::Linear => {
+                if (vstop < vstart && vstep >= 0.0) || vstep == 0.0 || vstep.is_nan() {
+                    return Err(ArC2Error::RampOperationError(vstart, vstop, vstep));
+                }
+                let steps = f32::ceil((vstop - vstart)/vstep) as usize;
+                (0..steps).map(|idx| vstart + vstep*(idx as f32)).collect()
+            },
+            RampShape::Triangle | RampShape::Bipolar => {
+                if vstep == 0.0 || vstep.is_nan() {
+                    return Err(ArC2Error::RampOperationError(vstart, vstop, vstep));
+                }
+                // Down legs are the reverse of the up legs so that both
+                // directions sample exactly the same voltages
+                let up = __leg(vstart, vstop, vstep);
+                let mut res = up.clone();
+                res.extend(up.iter().rev().skip(1));
+                if *self == RampShape::Bipolar {
+                    let neg = __leg(vstart, -vstop, vstep);
+                    res.extend_from_slice(&neg[1..]);
+                    res.extend(neg.iter().rev().skip(1));
+                }
+                res
+            },
+            RampShape::Logarithmic(per_decade) => {
+                if *per_decade == 0 || vstart == 0.0 || vstop == 0.0 ||
+                    vstart.signum() != vstop.signum() {
+                    return Err(ArC2Error::RampOperationError(vstart, vstop, vstep));
+                }
+                let ratio = 10.0f32.powf(1.0/(*per_decade as f32));
+                let steps = f32::ceil((vstop/vstart).log10() *
+                    (*per_decade as f32) - 1e-4).max(0.0) as usize;
+                (0..steps).map(|idx| vstart * ratio.powi(idx as i32)).collect()
+            }
+        };
+
+        let mut res: Vec<f32> = Vec::with_capacity(cycle.len()*cycles);
+        for c in 0..cycles {
+            match self {
+                RampShape::Triangle | RampShape::Bipolar if c > 0 => {
+                    res.extend_from_slice(&cycle[1..]);
+                },
+                _ => { res.extend_from_slice(&cycle); }
+            }
+        }
+
+        Ok(res)
+    }
+}
+
+/// Amplitude policy for closed-loop programming
+///
+/// This controls how the pulse amplitude evolves during
+/// [`Instrument::program_to_target`]. With `FixedStep(vstep)` the amplitude
+/// is increased by `vstep` after every pulse that fails to bring the device
+/// within tolerance. `Adaptive(min, max)` scales the step with the distance
+/// from the target (in decades of resistance), bounded between `min` and
+/// `max`. `Bisection` narrows down the amplitude window of each polarity:
+/// pulses that fall short raise the lower bound of the window whereas
+/// pulses that overshoot the target lower its upper bound. For `FixedStep`
+/// and `Adaptive` the amplitude is reset to its starting value every time
+/// polarity is switched. Steps are always given as positive values and
+/// apply to the amplitude of the pulse regardless of polarity.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProgramPolicy {
+    FixedStep(f32),
+    Adaptive(f32, f32),
+    Bisection
+}
+
+/// Input encoding for MAC operations
+///
+/// This controls how input values are applied by [`Instrument::mac_encoded`].
+/// With `Amplitude(vmax)` inputs are voltages applied directly, as with
+/// [`Instrument::mac`], but clipped to ±`vmax` to stay clear of disturb
+/// limits. The two other schemes apply a fixed amplitude, which makes them
+/// insensitive to nonlinear I–V characteristics, and expect inputs normalised
+/// to [-1, 1] (values outside are clipped); the sign of the input selects the
+/// polarity. `PulseWidth(amplitude, slots)` splits the read window into `slots`
+/// consecutive reads and each input is applied for a number of slots
+/// proportional to its magnitude; the currents are then integrated over all
+/// slots. `BitSerial(amplitude, bits)` quantises input magnitudes to `bits`
+/// bits (up to 16) and applies one bit-plane per read, recombining the results
+/// with shift-and-add. For both, results are in the same units as an
+/// amplitude-encoded MAC with inputs of `amplitude × input` volts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MACEncoding {
+    Amplitude(f32),
+    PulseWidth(f32, usize),
+    BitSerial(f32, u32)
+}
+
 /// Read-out mode for bulk memory reads
 ///
 /// This is primarily used with [`Instrument::pick_one`] to read a block
@@ -301,6 +549,480 @@ impl WaitFor {
     }
 }
 
+/// Generate a logarithmically spaced time schedule
+///
+/// This will produce all durations of the form `m × 10ⁿ` seconds, with `m` taken
+/// from `mantissas`, that lie within `[start, stop]` in ascending order. For
+/// instance `&[1.0, 2.0, 5.0]` produces the common 1 s, 2 s, 5 s, 10 s, …
+/// sequence. This is typically used with [`Instrument::retention`].
+///
+/// ```
+/// use libarc2::log_schedule;
+/// use std::time::Duration;
+///
+/// let schedule = log_schedule(Duration::from_secs(1), Duration::from_secs(60),
+///     &[1.0, 2.0, 5.0]);
+/// let secs: Vec<u64> = schedule.iter().map(|d| d.as_secs()).collect();
+/// assert_eq!(secs, vec![1, 2, 5, 10, 20, 50]);
+/// ```
+pub fn log_schedule(start: time::Duration, stop: time::Duration, mantissas: &[f64])
+    -> Vec<time::Duration> {
+
+    let mut result: Vec<time::Duration> = Vec::new();
+
+    if start > stop || start.as_secs_f64() <= 0.0 {
+        return result;
+    }
+
+    let mut decade = start.as_secs_f64().log10().floor() as i32;
+
+    loop {
+        let base = 10f64.powi(decade);
+
+        if base > stop.as_secs_f64() {
+            break;
+        }
+
+        for m in mantissas {
+            // round to the nearest μs to avoid floating point residue
+            let secs = (m * base * 1e6).round() / 1e6;
+            let t = time::Duration::from_secs_f64(secs);
+            if t >= start && t <= stop {
+                result.push(t);
+            }
+        }
+
+        decade += 1;
+    }
+
+    result.sort();
+    result.dedup();
+
+    result
+}
+
+/// Map a signed weight matrix onto differential conductance pairs
+///
+/// Weights are given as a matrix with one row per input channel and one
+/// column per output pair, so that output `k` is `Σᵢ Vᵢ·Wᵢₖ`. Every weight is
+/// mapped onto a `(G⁺, G⁻)` pair with `G⁺ - G⁻ = w·gref`, the smaller of the
+/// two sitting at `gmin`. The function returns programming targets as
+/// `(low, high, resistance)` tuples for all crosspoints between `inp_chans`
+/// and the positive and negative channels of `out_pairs`, in a format that
+/// can be passed directly to [`Instrument::program_to_target_batched`]. Use
+/// [`Instrument::mac_differential`] with the same `gref` to read the
+/// results back in weight units.
+///
+/// ```
+/// use libarc2::signed_weight_targets;
+///
+/// let weights = vec![vec![1.0, -0.5]];
+/// let targets = signed_weight_targets(&weights, &[16], &[(0, 1), (2, 3)],
+///     1e-4, 1e-5).unwrap();
+///
+/// assert_eq!(targets.len(), 4);
+/// // positive weight: G⁺ = gmin + w·gref, G⁻ = gmin
+/// assert!((1.0/targets[0].2 - 1.1e-4).abs() < 1e-9);
+/// assert!((1.0/targets[1].2 - 1e-5).abs() < 1e-9);
+/// // negative weight: G⁺ = gmin, G⁻ = gmin + |w|·gref
+/// assert!((1.0/targets[2].2 - 1e-5).abs() < 1e-9);
+/// assert!((1.0/targets[3].2 - 6e-5).abs() < 1e-9);
+/// ```
+pub fn signed_weight_targets(weights: &[Vec<f32>], inp_chans: &[usize],
+    out_pairs: &[(usize, usize)], gref: f32, gmin: f32)
+    -> Result<Vec<(usize, usize, f32)>, ArC2Error> {
+
+    if weights.len() != inp_chans.len() {
+        return Err(ArC2Error::DimensionError(weights.len(), inp_chans.len()));
+    }
+
+    for g in &[gref, gmin] {
+        if g.is_nan() || *g <= 0.0 {
+            return Err(ArC2Error::InvalidValue(*g));
+        }
+    }
+
+    let mut targets: Vec<(usize, usize, f32)> =
+        Vec::with_capacity(2 * inp_chans.len() * out_pairs.len());
+
+    for (row, inp) in weights.iter().zip(inp_chans) {
+        if row.len() != out_pairs.len() {
+            return Err(ArC2Error::DimensionError(row.len(), out_pairs.len()));
+        }
+        for (w, (pos, neg)) in row.iter().zip(out_pairs) {
+            if w.is_nan() {
+                return Err(ArC2Error::InvalidValue(*w));
+            }
+            let (gpos, gneg) = if *w >= 0.0 {
+                (gmin + w * gref, gmin)
+            } else {
+                (gmin, gmin - w * gref)
+            };
+            targets.push((*inp, *pos, 1.0 / gpos));
+            targets.push((*inp, *neg, 1.0 / gneg));
+        }
+    }
+
+    Ok(targets)
+}
+
+/// Placement of a part of a logical weight matrix on the array
+///
+/// A tile maps the block of a logical weight matrix `W` (as in `y = W·x`)
+/// spanning `outputs` × `inputs` onto physical input and output channels,
+/// with input `inputs.start + k` applied on `inp_chans[k]` and output
+/// `outputs.start + j` read from `out_chans[j]`. Tiles with the same `slot`
+/// can be present on the array at the same time (for instance on different
+/// channel groups) whereas a change of `slot` signifies that the array must
+/// be reprogrammed. Tiles are typically generated with [`tile_layout`] and
+/// evaluated with [`Instrument::mac_tiled`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct MACTile {
+    pub outputs: std::ops::Range<usize>,
+    pub inputs: std::ops::Range<usize>,
+    pub inp_chans: Vec<usize>,
+    pub out_chans: Vec<usize>,
+    pub slot: usize
+}
+
+impl MACTile {
+
+    /// Extract the conductances of this tile from the logical weight matrix
+    /// `weights` (one row per output). The result has one row per input
+    /// channel and one column per output channel, which is the layout expected
+    /// by [`Instrument::program_matrix`].
+    pub fn sub_matrix(&self, weights: &[Vec<f32>]) -> Vec<Vec<f32>> {
+        self.inputs.clone()
+            .map(|i| self.outputs.clone().map(|o| weights[o][i]).collect())
+            .collect()
+    }
+}
+
+/// Partition an `m`×`n` weight matrix into tiles that fit the specified channels
+///
+/// This will split a logical weight matrix with `m` outputs and `n` inputs into
+/// tiles of at most `inp_chans.len()` inputs and `out_chans.len()` outputs,
+/// all of them placed on the same channels. As a consequence every tile gets
+/// its own slot and the array must be reprogrammed between tiles (see
+/// [`Instrument::mac_tiled`]). If different channel groups of the array are
+/// used for different tiles these can be built by hand with a common slot.
+///
+/// ```
+/// use libarc2::tile_layout;
+///
+/// let tiles = tile_layout(40, 20, &(0..16).collect::<Vec<usize>>(),
+///     &(16..32).collect::<Vec<usize>>());
+///
+/// // 40 outputs need 3 tiles of 16, 20 inputs 2 tiles of 16
+/// assert_eq!(tiles.len(), 6);
+/// assert_eq!(tiles[5].outputs, 32..40);
+/// assert_eq!(tiles[5].inputs, 16..20);
+/// assert_eq!(tiles[5].inp_chans, vec![0, 1, 2, 3]);
+/// assert_eq!(tiles[5].out_chans.len(), 8);
+/// ```
+pub fn tile_layout(m: usize, n: usize, inp_chans: &[usize], out_chans: &[usize])
+    -> Vec<MACTile> {
+
+    let mut tiles: Vec<MACTile> = Vec::new();
+
+    if inp_chans.is_empty() || out_chans.is_empty() {
+        return tiles;
+    }
+
+    for o in (0..m).step_by(out_chans.len()) {
+        let outputs = o..(o + out_chans.len()).min(m);
+        for i in (0..n).step_by(inp_chans.len()) {
+            let inputs = i..(i + inp_chans.len()).min(n);
+            tiles.push(MACTile {
+                inp_chans: inp_chans[..inputs.len()].to_vec(),
+                out_chans: out_chans[..outputs.len()].to_vec(),
+                outputs: outputs.clone(),
+                inputs: inputs,
+                slot: tiles.len()
+            });
+        }
+    }
+
+    tiles
+}
+
+/// Correction for analog MAC non-idealities
+///
+/// This holds a per-output `gain` and `offset` and, optionally, a square
+/// `correction` matrix that accounts for crosstalk between outputs. A raw
+/// MAC output vector `m` is corrected as `A·(gain∘m + offset)`, where `A` is
+/// the correction matrix, or the identity if there is none. Calibrations are
+/// typically produced by [`Instrument::calibrate_mac`] and can be stored as
+/// plain text, using [`ToString`] and [`str::parse`], to be reused across
+/// sessions.
+///
+/// ```
+/// use libarc2::MACCalibration;
+///
+/// let cal = MACCalibration {
+///     gain: vec![2.0, -1.0],
+///     offset: vec![0.5, 0.0],
+///     correction: None
+/// };
+/// assert_eq!(cal.apply(&[1.0, 3.0]), vec![2.5, -3.0]);
+///
+/// let text = cal.to_string();
+/// let restored: MACCalibration = text.parse().unwrap();
+/// assert_eq!(restored, cal);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct MACCalibration {
+    pub gain: Vec<f32>,
+    pub offset: Vec<f32>,
+    pub correction: Option<Vec<Vec<f32>>>
+}
+
+impl MACCalibration {
+
+    /// Apply the calibration to a raw MAC output vector
+    pub fn apply(&self, measured: &[f32]) -> Vec<f32> {
+        let corrected: Vec<f32> = measured.iter()
+            .zip(self.gain.iter().zip(&self.offset))
+            .map(|(m, (g, o))| g * m + o)
+            .collect();
+
+        match self.correction {
+            Some(ref a) => a.iter()
+                .map(|row| row.iter().zip(&corrected).map(|(a, c)| a * c).sum())
+                .collect(),
+            None => corrected
+        }
+    }
+}
+
+impl std::fmt::Display for MACCalibration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+
+        fn __line(values: &[f32]) -> String {
+            values.iter().map(|v| v.to_string()).collect::<Vec<String>>().join(" ")
+        }
+
+        writeln!(f, "# libarc2 MAC calibration")?;
+        writeln!(f, "gain {}", __line(&self.gain))?;
+        writeln!(f, "offset {}", __line(&self.offset))?;
+        if let Some(ref a) = self.correction {
+            for row in a {
+                writeln!(f, "correction {}", __line(row))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for MACCalibration {
+    type Err = ArC2Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+
+        let mut gain: Option<Vec<f32>> = None;
+        let mut offset: Option<Vec<f32>> = None;
+        let mut correction: Vec<Vec<f32>> = Vec::new();
+
+        for line in s.lines().map(|l| l.trim()) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut fields = line.split_whitespace();
+            let key = fields.next().unwrap_or("");
+            let values = fields.map(|v| v.parse::<f32>())
+                .collect::<Result<Vec<f32>, _>>()
+                .map_err(|e| ArC2Error::CalibrationError(format!("{}: {}", key, e)))?;
+
+            match key {
+                "gain" => { gain = Some(values); },
+                "offset" => { offset = Some(values); },
+                "correction" => { correction.push(values); },
+                _ => {
+                    return Err(ArC2Error::CalibrationError(
+                        format!("unknown entry {}", key)));
+                }
+            }
+        }
+
+        let gain = gain.ok_or(ArC2Error::CalibrationError("missing gain".to_string()))?;
+        let offset = offset.ok_or(ArC2Error::CalibrationError("missing offset".to_string()))?;
+
+        if offset.len() != gain.len() {
+            return Err(ArC2Error::DimensionError(offset.len(), gain.len()));
+        }
+
+        for row in &correction {
+            if row.len() != gain.len() {
+                return Err(ArC2Error::DimensionError(row.len(), gain.len()));
+            }
+        }
+
+        let correction = match correction.len() {
+            0 => None,
+            n if n == gain.len() => Some(correction),
+            n => { return Err(ArC2Error::DimensionError(n, gain.len())); }
+        };
+
+        Ok(MACCalibration { gain, offset, correction })
+    }
+}
+
+#[cfg(test)]
+mod maccalibration_tests {
+
+    use super::{MACCalibration, ArC2Error};
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn parse_roundtrip() {
+        let cal = MACCalibration {
+            gain: vec![1.5, 0.5],
+            offset: vec![-1e-6, 2e-6],
+            correction: Some(vec![vec![1.0, 0.1], vec![-0.1, 1.0]])
+        };
+        let restored: MACCalibration = cal.to_string().parse().unwrap();
+        assert_eq!(restored, cal);
+    }
+
+    #[test]
+    fn parse_missing_entries() {
+        assert_matches!("offset 0.0 0.0".parse::<MACCalibration>(),
+            Err(ArC2Error::CalibrationError(_)));
+        assert_matches!("gain 1.0 1.0".parse::<MACCalibration>(),
+            Err(ArC2Error::CalibrationError(_)));
+        assert_matches!("".parse::<MACCalibration>(),
+            Err(ArC2Error::CalibrationError(_)));
+    }
+
+    #[test]
+    fn parse_invalid_entries() {
+        // not a number
+        assert_matches!("gain 1.0 foo\noffset 0.0 0.0".parse::<MACCalibration>(),
+            Err(ArC2Error::CalibrationError(_)));
+        // unknown key
+        assert_matches!("gain 1.0\noffset 0.0\nscale 2.0".parse::<MACCalibration>(),
+            Err(ArC2Error::CalibrationError(_)));
+    }
+
+    #[test]
+    fn parse_dimension_mismatch() {
+        assert_matches!("gain 1.0 1.0\noffset 0.0".parse::<MACCalibration>(),
+            Err(ArC2Error::DimensionError(1, 2)));
+        // correction rows of the wrong length
+        assert_matches!("gain 1.0 1.0\noffset 0.0 0.0\ncorrection 1.0\ncorrection 1.0"
+            .parse::<MACCalibration>(), Err(ArC2Error::DimensionError(1, 2)));
+        // not enough correction rows
+        assert_matches!("gain 1.0 1.0\noffset 0.0 0.0\ncorrection 1.0 0.0"
+            .parse::<MACCalibration>(), Err(ArC2Error::DimensionError(1, 2)));
+    }
+
+    #[test]
+    fn parse_comments_and_blanks() {
+        let cal: MACCalibration = "# comment\n\n  gain 2.0\noffset 1.0\n".parse().unwrap();
+        assert_eq!(cal.gain, vec![2.0]);
+        assert_eq!(cal.offset, vec![1.0]);
+        assert_eq!(cal.correction, None);
+        assert_eq!(cal.apply(&[3.0]), vec![7.0]);
+    }
+}
+
+/// Solve `A·x = b` for a square system with Gaussian elimination and
+/// partial pivoting. Returns `None` if the system is singular, including
+/// systems that are only non-singular due to rounding errors.
+fn _solve_linear(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+
+    let n = b.len();
+
+    // pivots are compared against the magnitude of the matrix as the
+    // entries of calibration systems can be arbitrarily small
+    let scale = a.iter().flatten().fold(0.0f64, |m, v| m.max(v.abs()));
+    let threshold = scale * 1e-12;
+
+    for col in 0..n {
+        let pivot = (col..n).max_by(|i, j| a[*i][col].abs()
+            .partial_cmp(&a[*j][col].abs())
+            .unwrap_or(std::cmp::Ordering::Equal))?;
+
+        if a[pivot][col].abs() <= threshold {
+            return None;
+        }
+
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+
+        for row in (col+1)..n {
+            let factor = a[row][col] / a[col][col];
+            for k in col..n {
+                a[row][k] -= factor * a[col][k];
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+
+    let mut x = vec![0.0f64; n];
+    for row in (0..n).rev() {
+        let sum: f64 = ((row+1)..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+
+    Some(x)
+}
+
+#[cfg(test)]
+mod solve_linear_tests {
+
+    use super::_solve_linear;
+
+    #[test]
+    fn solve_regular() {
+        // 2x + y = 5, x + 3y = 10 => x = 1, y = 3
+        let a = vec![vec![2.0, 1.0], vec![1.0, 3.0]];
+        let x = _solve_linear(a, vec![5.0, 10.0]).unwrap();
+        assert!((x[0] - 1.0).abs() < 1e-12);
+        assert!((x[1] - 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn solve_needs_pivoting() {
+        // zero on the diagonal of the first row
+        let a = vec![vec![0.0, 1.0, 0.0], vec![1.0, 0.0, 0.0], vec![0.0, 0.0, 2.0]];
+        let x = _solve_linear(a, vec![4.0, 5.0, 6.0]).unwrap();
+        assert!((x[0] - 5.0).abs() < 1e-12);
+        assert!((x[1] - 4.0).abs() < 1e-12);
+        assert!((x[2] - 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn solve_singular() {
+        let a = vec![vec![1.0, 2.0], vec![2.0, 4.0]];
+        assert_eq!(_solve_linear(a, vec![1.0, 2.0]), None);
+
+        // singular only up to rounding errors
+        let a = vec![vec![1.0, 2.0], vec![3.0, 6.0]];
+        assert_eq!(_solve_linear(a, vec![1.0, 3.0]), None);
+
+        let a = vec![vec![0.1, 0.2, 0.3], vec![0.4, 0.5, 0.6], vec![0.7, 0.8, 0.9]];
+        assert_eq!(_solve_linear(a, vec![1.0, 1.0, 1.0]), None);
+
+        let a = vec![vec![0.0, 0.0], vec![0.0, 0.0]];
+        assert_eq!(_solve_linear(a, vec![0.0, 0.0]), None);
+    }
+}
+
+/// Gate terminal of a three-terminal device
+///
+/// Used with [`Instrument::generate_gate_sweep`] to select where the gate
+/// voltage is applied. `Channel(chan)` drives the gate from one of the regular
+/// ArC2 channels whereas `Aux(func)` uses one of the arbitrary AUX supplies;
+/// only [`AuxDACFn::ARB1`] to [`AuxDACFn::ARB4`] are valid in that case.
+#[derive(Clone, Copy)]
+pub enum GateSource {
+    Channel(usize),
+    Aux(AuxDACFn)
+}
+
 #[derive(Copy, Clone, PartialEq)]
 pub enum LogicLevel {
     LL1V8,
@@ -319,6 +1041,67 @@ impl Into<f32> for LogicLevel {
 }
 
 
+/// Handle to a background operation
+///
+/// This is returned by operations that run on a separate thread, such as
+/// [`Instrument::read_train`], and can be used to cancel the operation, query
+/// its progress or wait for it to finish. Dropping the handle does not stop the
+/// operation; it will keep running until it completes or is cancelled through
+/// [`Instrument::cancel_op`]. Every operation has its own cancellation flag so
+/// cancelling through the handle does not affect other operations. If the
+/// operation is cancelled, fails or panics all channels are grounded.
+pub struct OpHandle {
+    _thread: Option<thread::JoinHandle<Result<(), ArC2Error>>>,
+    _cancel: Arc<atomic::AtomicBool>,
+    _progress: Arc<atomic::AtomicUsize>,
+    _total: Option<usize>
+}
+
+impl OpHandle {
+
+    /// Request cancellation of the operation. The operation will stop at the
+    /// earliest opportunity; use [`OpHandle::join`] to wait for it.
+    pub fn cancel(&self) {
+        if !self.is_finished() {
+            self._cancel.store(true, atomic::Ordering::Relaxed);
+        }
+    }
+
+    /// Wait for the operation to finish and return its result. Cancelled
+    /// operations return `Ok(())` unless an error occurred before or while
+    /// grounding the channels.
+    pub fn join(mut self) -> Result<(), ArC2Error> {
+        match self._thread.take() {
+            Some(thread) => match thread.join() {
+                Ok(res) => res,
+                Err(err) => std::panic::resume_unwind(err)
+            },
+            None => Ok(())
+        }
+    }
+
+    /// Check if the operation has finished, successfully or not.
+    pub fn is_finished(&self) -> bool {
+        match self._thread {
+            Some(ref thread) => thread.is_finished(),
+            None => true
+        }
+    }
+
+    /// Number of steps completed so far. What a step is depends on the
+    /// operation, for instance reads for [`Instrument::read_train`] or
+    /// cycles for [`Instrument::endurance`].
+    pub fn progress(&self) -> usize {
+        self._progress.load(atomic::Ordering::Relaxed)
+    }
+
+    /// Total number of steps of the operation, if known in advance.
+    pub fn total(&self) -> Option<usize> {
+        self._total
+    }
+}
+
+
 /// ArC2 entry level object
 ///
 /// `Instrument` implements the frontend for the ArC2. Its role is essentially
@@ -350,11 +1133,15 @@ pub struct Instrument {
     _receiver: Arc<Mutex<Receiver<Option<Chunk>>>>,
     // A thread has been spawned
     _op_running: Arc<atomic::AtomicBool>,
+    // Cancellation flags of the running threads; each thread should
+    // stop at the earliest opportunity once its flag is raised
+    _op_cancel: Arc<Mutex<Vec<Arc<atomic::AtomicBool>>>>,
 
     // State tracking
     _tia_state: ChanMask,
     _hard_gnds: ChanMask,
-    _ac_gnds: ChanMask
+    _ac_gnds: ChanMask,
+    _cur_src: ChanMask
 }
 
 /// Find available device IDs.
@@ -473,55 +1260,317 @@ fn _adc_to_voltage(val: u32) -> f32 {
 
 }
 
+/// Split the lines of a 32×32 array that are not in `selected` into lines of
+/// the same type as `low` (rows or columns) and lines of the opposite type.
+/// Channel `low` is never included in either list.
+fn _array_unselected(low: usize, selected: &[usize]) -> (Vec<usize>, Vec<usize>) {
 
-#[cfg(all(any(target_os = "windows", target_os = "linux"), target_arch = "x86_64"))]
-impl Instrument {
+    let (same, opposite) = if (low < 16) || ((32 <= low) && (low < 48)) {
+        (&*ALL_BITS, &*ALL_WORDS)
+    } else {
+        (&*ALL_WORDS, &*ALL_BITS)
+    };
 
-    /// Create a new Instrument with a known ID.  Use [`find_ids`]
-    /// to discover devices. Set `retained_mode` to `true` to defer
-    /// writing instructions until an [`Instrument::execute()`]
-    /// has been called explicitly.
-    pub fn open(id: i32, retained_mode: bool) -> Result<Instrument, ArC2Error> {
+    let same: Vec<usize> = same.iter()
+        .filter(|c| **c != low && !selected.contains(c))
+        .map(|c| *c)
+        .collect();
+    let opposite: Vec<usize> = opposite.iter()
+        .filter(|c| **c != low && !selected.contains(c))
+        .map(|c| *c)
+        .collect();
 
-        if !find_ids()?.contains(&id) {
-            return Err(ArC2Error::InvalidID(id));
-        }
+    (same, opposite)
+}
 
-        let buffer: Option<Arc<RwLock<Vec<u8>>>>;
+#[cfg(test)]
+mod array_unselected_tests {
 
-        // If in retained mode preallocate space for 10×768 instructions.
-        // If not the instruction buffer is not used.
-        if retained_mode {
-            buffer = Some(Arc::new(RwLock::new(Vec::with_capacity(10usize*INSTRCAP))));
-        } else {
-            buffer = None;
-        }
+    use super::_array_unselected;
 
-        let (sender, receiver) = channel::<Option<Chunk>>();
+    #[test]
+    fn unselected_from_bit() {
+        let (same, opposite) = _array_unselected(3, &[19, 20]);
 
-        match bl::Device::open(id) {
-            Ok(d) => Ok(Instrument {
-                efm: Arc::new(Mutex::new(d)),
-                instr_buffer: buffer,
-                memman: Arc::new(RwLock::new(MemMan::new())),
-                _sender: sender,
-                _receiver: Arc::new(Mutex::new(receiver)),
-                _op_running: Arc::new(atomic::AtomicBool::new(false)),
-                _tia_state: ChanMask::all(),
-                _hard_gnds: ChanMask::none(),
-                _ac_gnds: ChanMask::none()
+        // low channel and selected lines are excluded
+        assert_eq!(same.len(), 31);
+        assert!(!same.contains(&3));
+        assert!(same.iter().all(|c| *c < 16 || (32 <= *c && *c < 48)));
 
-            }),
-            Err(err) => Err(ArC2Error::FPGAError(err))
-        }
+        assert_eq!(opposite.len(), 30);
+        assert!(!opposite.contains(&19) && !opposite.contains(&20));
+        assert!(opposite.iter().all(|c| (16 <= *c && *c < 32) || *c >= 48));
     }
 
-    /// Load an FPGA bitstream from a file.
-    pub fn load_firmware(&self, path: &str) -> Result<(), ArC2Error> {
-        let _efm = self.efm.clone();
-        let efm = _efm.lock().unwrap();
-        /*match efm.program_from_file(&path) {
-            Ok(()) => { Ok(()) },
+    #[test]
+    fn unselected_from_word() {
+        let (same, opposite) = _array_unselected(50, &[0, 33, 51]);
+
+        assert_eq!(same.len(), 30);
+        assert!(!same.contains(&50) && !same.contains(&51));
+        assert!(same.iter().all(|c| (16 <= *c && *c < 32) || *c >= 48));
+
+        assert_eq!(opposite.len(), 30);
+        assert!(!opposite.contains(&0) && !opposite.contains(&33));
+        assert!(opposite.iter().all(|c| *c < 16 || (32 <= *c && *c < 48)));
+    }
+
+    #[test]
+    fn unselected_all_selected() {
+        let all: Vec<usize> = (0..64).collect();
+        let (same, opposite) = _array_unselected(0, &all);
+        assert!(same.is_empty());
+        assert!(opposite.is_empty());
+    }
+}
+
+/// Partition high speed pulses into compatible rounds
+///
+/// This will split a list of high speed pulses, given as `(channel, active_v,
+/// normal_v, width)` tuples, into the minimum number of rounds that can each
+/// be applied with a single HS configuration (see
+/// [`Instrument::pulse_slice_fast_open`]). Within a round all pulses of a
+/// cluster (channels `8k` to `8k+7`) must share the same width and polarity,
+/// where polarity is determined by whether `active_v` is below `normal_v`,
+/// and every channel can only be pulsed once. Clusters are independent so
+/// the number of rounds is that of the most demanding cluster. The function
+/// returns the indices of the pulses in every round. Identical pulses on the
+/// same channel retain their relative order but pulses of different width or
+/// polarity might be reordered. An [`ArC2Error::InvalidChannel`] error is
+/// raised for channels that do not exist.
+///
+/// ```
+/// use libarc2::hs_pulse_rounds;
+///
+/// let pulses = vec![(0, 1.0, 0.0, 100), (1, -1.0, 0.0, 100), (8, 1.0, 0.0, 200),
+///     (9, 1.0, 0.0, 200), (0, 1.0, 0.0, 100)];
+/// let rounds = hs_pulse_rounds(&pulses).unwrap();
+///
+/// // cluster 0 needs three rounds: two for channel 0 and one
+/// // for the opposite polarity pulse on channel 1
+/// assert_eq!(rounds, vec![vec![0, 2, 3], vec![4], vec![1]]);
+/// ```
+pub fn hs_pulse_rounds(pulses: &[(usize, f32, f32, u128)]) -> Result<Vec<Vec<usize>>, ArC2Error> {
+
+    // rounds required by each cluster; these are independent across
+    // clusters so the k-th rounds of all clusters are merged together
+    let mut clusters: Vec<Vec<Vec<usize>>> = vec![Vec::new(); 8];
+
+    if let Some((chan, _, _, _)) = pulses.iter().find(|p| p.0 >= 64) {
+        return Err(ArC2Error::InvalidChannel(*chan));
+    }
+
+    for cl in 0..8 {
+        // pulses of the cluster grouped by (width, polarity) in order of
+        // first appearance
+        let mut classes: Vec<((u128, bool), Vec<usize>)> = Vec::new();
+
+        for (idx, (chan, active_v, normal_v, width)) in pulses.iter().enumerate() {
+            if *chan/8 != cl {
+                continue;
+            }
+            let key = (*width, vidx!(*active_v) <= vidx!(*normal_v));
+            match classes.iter_mut().find(|c| c.0 == key) {
+                Some(class) => { class.1.push(idx); },
+                None => { classes.push((key, vec![idx])); }
+            }
+        }
+
+        for (_, members) in classes {
+            let mut rounds: Vec<Vec<usize>> = Vec::new();
+            for idx in members {
+                let chan = pulses[idx].0;
+                match rounds.iter_mut().find(|r| r.iter().all(|i| pulses[*i].0 != chan)) {
+                    Some(round) => { round.push(idx); },
+                    None => { rounds.push(vec![idx]); }
+                }
+            }
+            clusters[cl].extend(rounds);
+        }
+    }
+
+    let nrounds = clusters.iter().map(|c| c.len()).max().unwrap_or(0);
+    let mut result: Vec<Vec<usize>> = vec![Vec::new(); nrounds];
+    for rounds in clusters {
+        for (k, round) in rounds.into_iter().enumerate() {
+            result[k].extend(round);
+        }
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod hs_pulse_rounds_tests {
+
+    use super::{hs_pulse_rounds, ArC2Error};
+    use assert_matches::assert_matches;
+
+    // Check that `rounds` is a valid partition of `pulses`: every pulse is
+    // used once and within a round channels are unique and every cluster
+    // has a single width and polarity
+    fn assert_valid(pulses: &[(usize, f32, f32, u128)], rounds: &[Vec<usize>]) {
+        let mut used = vec![0usize; pulses.len()];
+        for round in rounds {
+            assert!(!round.is_empty());
+            let mut chans: Vec<usize> = Vec::new();
+            let mut clusters: Vec<Option<(u128, bool)>> = vec![None; 8];
+            for idx in round {
+                used[*idx] += 1;
+                let (chan, active_v, normal_v, width) = pulses[*idx];
+                assert!(!chans.contains(&chan), "channel {} pulsed twice", chan);
+                chans.push(chan);
+                let key = (width, active_v <= normal_v);
+                match clusters[chan/8] {
+                    Some(k) => assert_eq!(k, key, "incompatible pulses on cluster {}", chan/8),
+                    None => { clusters[chan/8] = Some(key); }
+                }
+            }
+        }
+        assert!(used.iter().all(|u| *u == 1));
+    }
+
+    #[test]
+    fn rounds_empty() {
+        assert_eq!(hs_pulse_rounds(&[]).unwrap(), Vec::<Vec<usize>>::new());
+    }
+
+    #[test]
+    fn rounds_invalid_channel() {
+        let pulses = vec![(0, 1.0, 0.0, 100), (64, 1.0, 0.0, 100)];
+        assert_matches!(hs_pulse_rounds(&pulses),
+            Err(ArC2Error::InvalidChannel(64)));
+    }
+
+    #[test]
+    fn rounds_mixed_widths() {
+        // same cluster, same polarity, different widths
+        let pulses = vec![(0, 1.0, 0.0, 100), (1, 1.0, 0.0, 200), (2, 1.0, 0.0, 100),
+            (3, 1.0, 0.0, 300)];
+        let rounds = hs_pulse_rounds(&pulses).unwrap();
+        assert_eq!(rounds, vec![vec![0, 2], vec![1], vec![3]]);
+        assert_valid(&pulses, &rounds);
+    }
+
+    #[test]
+    fn rounds_independent_clusters() {
+        // two rounds on cluster 0 (same channel), one on cluster 1 of
+        // a different width; the latter is merged into the first round
+        let pulses = vec![(0, 1.0, 0.0, 100), (0, 1.0, 0.0, 100), (8, -1.0, 0.0, 200)];
+        let rounds = hs_pulse_rounds(&pulses).unwrap();
+        assert_eq!(rounds, vec![vec![0, 2], vec![1]]);
+    }
+
+    #[test]
+    fn rounds_many_pulses() {
+        // more pulses than channels, spread over all clusters with
+        // pseudorandom widths and polarities
+        let mut state: u32 = 0x2545f491;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        };
+
+        let pulses: Vec<(usize, f32, f32, u128)> = (0..200)
+            .map(|_| {
+                let chan = (next() % 64) as usize;
+                let v = if next() % 2 == 0 { 1.0 } else { -1.0 };
+                let width = 100 * (1 + (next() % 3) as u128);
+                (chan, v, 0.0, width)
+            })
+            .collect();
+
+        let rounds = hs_pulse_rounds(&pulses).unwrap();
+        assert_valid(&pulses, &rounds);
+        assert!(rounds.len() < pulses.len());
+    }
+}
+
+/// Resistance from a current read done at `vread`
+fn _resistance(vread: f32, current: f32) -> f32 {
+    (vread / current).abs()
+}
+
+/// Position of `res` relative to the tolerance window around `target`:
+/// 1 for above, -1 for below and 0 for within.
+fn _tolerance_side(res: f32, target: f32, tolerance: f32) -> i8 {
+    if res > target * (1.0 + tolerance) {
+        1
+    } else if res < target * (1.0 - tolerance) {
+        -1
+    } else {
+        0
+    }
+}
+
+/// Group crosspoints by their low channel, given in `lows`, in order of
+/// first appearance. Groups hold the indices of their crosspoints.
+fn _group_by_low<I: IntoIterator<Item=usize>>(lows: I) -> Vec<(usize, Vec<usize>)> {
+
+    let mut groups: Vec<(usize, Vec<usize>)> = Vec::new();
+    for (idx, low) in lows.into_iter().enumerate() {
+        match groups.iter_mut().find(|g| g.0 == low) {
+            Some(group) => { group.1.push(idx); },
+            None => { groups.push((low, vec![idx])); }
+        }
+    }
+
+    groups
+}
+
+
+#[cfg(all(any(target_os = "windows", target_os = "linux"), target_arch = "x86_64"))]
+impl Instrument {
+
+    /// Create a new Instrument with a known ID.  Use [`find_ids`]
+    /// to discover devices. Set `retained_mode` to `true` to defer
+    /// writing instructions until an [`Instrument::execute()`]
+    /// has been called explicitly.
+    pub fn open(id: i32, retained_mode: bool) -> Result<Instrument, ArC2Error> {
+
+        if !find_ids()?.contains(&id) {
+            return Err(ArC2Error::InvalidID(id));
+        }
+
+        let buffer: Option<Arc<RwLock<Vec<u8>>>>;
+
+        // If in retained mode preallocate space for 10×768 instructions.
+        // If not the instruction buffer is not used.
+        if retained_mode {
+            buffer = Some(Arc::new(RwLock::new(Vec::with_capacity(10usize*INSTRCAP))));
+        } else {
+            buffer = None;
+        }
+
+        let (sender, receiver) = channel::<Option<Chunk>>();
+
+        match bl::Device::open(id) {
+            Ok(d) => Ok(Instrument {
+                efm: Arc::new(Mutex::new(d)),
+                instr_buffer: buffer,
+                memman: Arc::new(RwLock::new(MemMan::new())),
+                _sender: sender,
+                _receiver: Arc::new(Mutex::new(receiver)),
+                _op_running: Arc::new(atomic::AtomicBool::new(false)),
+                _op_cancel: Arc::new(Mutex::new(Vec::new())),
+                _tia_state: ChanMask::all(),
+                _hard_gnds: ChanMask::none(),
+                _ac_gnds: ChanMask::none(),
+                _cur_src: ChanMask::none()
+
+            }),
+            Err(err) => Err(ArC2Error::FPGAError(err))
+        }
+    }
+
+    /// Load an FPGA bitstream from a file.
+    pub fn load_firmware(&self, path: &str) -> Result<(), ArC2Error> {
+        let _efm = self.efm.clone();
+        let efm = _efm.lock().unwrap();
+        /*match efm.program_from_file(&path) {
+            Ok(()) => { Ok(()) },
             Err(err) => { Err(format!("Could not program FPGA: {}", err)) }
         }*/
         efm.program_from_file(&path).map_err(|e| ArC2Error::FPGAError(e) )
@@ -855,6 +1904,9 @@ impl Instrument {
     pub fn float_all(&mut self) -> Result<&mut Self, ArC2Error> {
         self.connect_to_gnd(&[])?;
         self.connect_to_ac_gnd(&[])?;
+        if !self._cur_src.is_empty() {
+            self.connect_to_current_source(&[])?;
+        }
         self.process(&*CHAN_FLOAT_ALL)?;
         self._tia_state = ChanMask::all();
 
@@ -863,6 +1915,9 @@ impl Instrument {
 
     /// Ground all channels reverting them to VoltArb
     pub fn ground_all(&mut self) -> Result<&mut Self, ArC2Error> {
+        if !self._cur_src.is_empty() {
+            self.connect_to_current_source(&[])?;
+        }
         self.process(&*RESET_DAC)?;
         self.process(&*UPDATE_DAC)?;
         self.add_delay(30_000u128)?;
@@ -896,7 +1951,8 @@ impl Instrument {
 
     /// Modify previously configured channels by switching them to ground. Use an
     /// empty channel list to release. This will clear any other hard ground/floating
-    /// instructions, but will maintain the state of AC grounds.
+    /// instructions, but will maintain the state of AC grounds and current source
+    /// connections.
     pub fn connect_to_gnd(&mut self, channels: &[usize]) -> Result<&mut Self, ArC2Error> {
 
         let mut chanmask = ChanMask::new();
@@ -907,7 +1963,7 @@ impl Instrument {
         self._hard_gnds = chanmask.clone();
 
         let mut instr = ModifyChannel::from_masks(&chanmask, &self._ac_gnds,
-            &ChanMask::none());
+            &self._cur_src);
         self.process(instr.compile())?;
 
         Ok(self)
@@ -946,7 +2002,8 @@ impl Instrument {
 
     /// Modify previously configured channels by switching them to a capacitor backed ground.
     /// Use an empty channel list to release. This will clear any previous AC ground/floating
-    /// instructions but will maintain the existing state of the hard grounds.
+    /// instructions but will maintain the existing state of the hard grounds and current
+    /// source connections.
     pub fn connect_to_ac_gnd(&mut self, channels: &[usize]) -> Result<&mut Self, ArC2Error> {
 
         let mut chanmask = ChanMask::new();
@@ -957,7 +2014,26 @@ impl Instrument {
         self._ac_gnds = chanmask.clone();
 
         let mut instr = ModifyChannel::from_masks(&self._hard_gnds, &chanmask,
-            &ChanMask::none());
+            &self._cur_src);
+        self.process(instr.compile())?;
+
+        Ok(self)
+
+    }
+
+    /// Modify previously configured channels by connecting them to the current source.
+    /// Use an empty channel list to release. This will clear any previous current
+    /// source connections but will maintain the existing state of the hard and AC
+    /// grounds. Typically this is done through [`Instrument::config_current_source`]
+    /// which will also configure the current source for the requested current.
+    pub fn connect_to_current_source(&mut self, channels: &[usize]) -> Result<&mut Self, ArC2Error> {
+
+        let chanmask = ChanMask::from_channels(channels);
+
+        self._cur_src = chanmask.clone();
+
+        let mut instr = ModifyChannel::from_masks(&self._hard_gnds, &self._ac_gnds,
+            &chanmask);
         self.process(instr.compile())?;
 
         Ok(self)
@@ -1417,27 +2493,50 @@ impl Instrument {
     fn _read_slice_inner(&mut self, low: usize, highs: &[usize], vread: u16)
         -> Result<Chunk, ArC2Error> {
 
+        self._read_biased_inner(&[(low, vread)], highs, None, false)
+    }
+
+    /// Common read functionality with an arbitrary number of biased channels, each one
+    /// at its own voltage, and several sense channels. Sense channels that are not
+    /// also included in `biased` are held at 0.0 V. If `base` is not `None` all
+    /// channels not included in either `biased` or `sense` will be set to the
+    /// specified raw DAC voltage. If `float_unselected` is `true` channels not
+    /// included in `biased` or `sense` will not be switched to VoltArb and will
+    /// maintain their existing state; this is typically used when these have been
+    /// previously opened with [`Instrument::open_channels`]. This function is
+    /// guaranteed never to flush the output.
+    fn _read_biased_inner(&mut self, biased: &[(usize, u16)], sense: &[usize], base: Option<u16>,
+        float_unselected: bool) -> Result<Chunk, ArC2Error> {
+
         let zero: u16 = vidx!(0.0);
 
-        // generate a list of dac settings
-        let mut actives: Vec<(u16, u16, u16)> = Vec::new();
-        actives.push((low as u16, vread, vread));
-        for high in highs {
-            actives.push((*high as u16, zero, zero));
+        // generate a list of dac settings; biased channels first and then
+        // any sense channels that have not been explicitly biased
+        let mut actives: Vec<(u16, u16, u16)> = Vec::with_capacity(biased.len() + sense.len());
+        for (chan, v) in biased {
+            actives.push((*chan as u16, *v, *v));
+        }
+        for chan in sense {
+            if !biased.iter().any(|(c, _)| c == chan) {
+                actives.push((*chan as u16, zero, zero));
+            }
         }
         let (mut upch, setdacs) = SetDAC::from_channels(&actives,
-            None, &ChannelState::VoltArb, &ChannelState::Maintain)?;
+            base.map(|b| (b, b)), &ChannelState::VoltArb,
+            &ChannelState::Maintain)?;
 
         // Is this necessary here?
         // Yes it is necessary, as the UP CH following this will transition
-        // channels to VoltArb so they (both low and high channels) *must be*
+        // channels to VoltArb so they (both biased and sense channels) *must be*
         // AMP PRPed to allow for the transition to happen without transients.
-        let mut chans_to_prep = highs.to_vec();
-        chans_to_prep.push(low);
-        // This actually needs to AMP PRP all channels as a base voltage is applied
-        // above so all channels will be set to VoltArb
-        //self.amp_prep(Some(&chans_to_prep))?;
-        self.amp_prep(None)?;
+        // This actually needs to AMP PRP all channels as all channels will be
+        // set to VoltArb below, unless unselected channels are left floating.
+        if float_unselected {
+            let chans_to_prep: Vec<usize> = actives.iter().map(|c| c.0 as usize).collect();
+            self.amp_prep(Some(&chans_to_prep))?;
+        } else {
+            self.amp_prep(None)?;
+        }
         self.process(upch.compile())?;
         // this is not necessary as C READ is the same as setting channels
         // as VoltArb
@@ -1450,15 +2549,18 @@ impl Instrument {
         self.process(&*UPDATE_DAC)?;
         self.add_delay(30_000u128)?;
 
-        // set all channels to Arbitrary Voltage
-        let mut channelconf =
-            UpdateChannel::from_regs_global_state(ChannelState::VoltArb);
-        self.process(channelconf.compile())?;
+        // set all channels to Arbitrary Voltage; if unselected channels are
+        // floating the UP CH above has already set the selected ones
+        if !float_unselected {
+            let mut channelconf =
+                UpdateChannel::from_regs_global_state(ChannelState::VoltArb);
+            self.process(channelconf.compile())?;
+        }
 
         // Prepare the ADC mask for the readout
         let mut adcmask = ChanMask::new();
 
-        for chan in highs {
+        for chan in sense {
             adcmask.set_enabled(*chan, true);
         }
 
@@ -1698,104 +2800,768 @@ impl Instrument {
         Ok(res)
     }
 
-    /// MAC operation with given input and output channels and input voltages
+    /// Perform a current read with arbitrary per-channel biasing
     ///
-    /// This function will perform multiply accumulation operation. Input channel list contains
-    /// input channels and corresponding input voltage. Then, the currents from output channels
-    /// will be collected.
-    pub fn mac(&mut self, inp_chans: &[(usize, f32)], out_chans: &[usize]) -> Result<Vec<f32>, ArC2Error> {
+    /// Argument `biased` expects an array of tuples following the format `(channel,
+    /// voltage)`. All biased channels are configured in a single pass, and then the
+    /// current is read from all channels included in `sense`. Sense channels not
+    /// included in `biased` will be held at 0.0 V. Unlike [`Instrument::read_one`]
+    /// voltages are applied to the channels *as is*; no sign inversion is done. This
+    /// allows for reading schemes where unselected lines are biased at different
+    /// voltages (such as V/2 or V/3 schemes) or multi-terminal devices, such as
+    /// transistors with a gate bias. This function will always return a 64-element
+    /// vector. Channels not included in `sense` will be replaced by `f32::NAN`.
+    ///
+    /// ```no_run
+    /// use libarc2::{Instrument};
+    /// # use libarc2::ArC2Error;
+    ///
+    /// # fn main() -> Result<(), ArC2Error> {
+    /// let mut arc2 = Instrument::open_with_fw(0, "fw.bin", true, true).unwrap();
+    ///
+    /// // Bias channel 3 at -0.2 V and channel 5 (a gate, for instance) at 1.2 V
+    /// // and read the current flowing into channel 19
+    /// let res = arc2.read_biased(&[(3, -0.2), (5, 1.2)], &[19])?;
+    /// println!("Current: {} A", res[19]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn read_biased(&mut self, biased: &[(usize, f32)], sense: &[usize])
+        -> Result<Vec<f32>, ArC2Error> {
 
-        // let mut results = Vec::with_capacity(out_chans.len());
-        let mut res : Vec<f32>;
-        let mut chunk: Chunk;
+        let input = Self::_biased_to_raw(biased)?;
 
         // Reset DAC configuration
         self.reset_dacs()?;
 
-        chunk = self._mac_inner(inp_chans, out_chans)?;
+        // Initiate a read operation, get the address of the data to be...
+        let mut chunk = self._read_biased_inner(&input, sense, None, false)?;
 
-        // Withdraw voltage from the biasing channels
+        // ... and finally withdraw voltage from the biasing channels
         self.ground_all_fast()?.execute()?;
         self.wait();
 
-        res = self.read_chunk(&mut chunk, &DataMode::All, &ReadType::Current)?;
+        let data = self.read_chunk(&mut chunk, &DataMode::All, &ReadType::Current)?;
 
-        // For each channel in `out_chans`, get the corresponding value from `res`
-        let values = out_chans.iter().map(|&chan| res[chan]).collect();
+        // Only keep the sense channels
+        let mut res = vec![f32::NAN; 64];
+        for chan in sense {
+            res[*chan] = data[*chan];
+        }
 
-        Ok(values)
+        Ok(res)
     }
 
-    ///
-    ///
-    fn _mac_inner(&mut self, inp_chans: &[(usize, f32)], out_chans: &[usize]) -> Result<Chunk, ArC2Error> {
+    /// Perform a current read with arbitrary per-channel biasing. This is the deferred
+    /// version of [`Instrument::read_biased`] and semantics of `biased` and `sense` are
+    /// the same. Setting `ground` to `true` will ground all involved channels after the
+    /// measurement has gone through. The result is added to the output buffer and can
+    /// be retrieved with [`Instrument::pick_one`] once the command buffer has been
+    /// executed.
+    pub fn read_biased_deferred(&mut self, biased: &[(usize, f32)], sense: &[usize],
+        ground: bool) -> Result<&mut Self, ArC2Error> {
 
-        let zero: u16 = vidx!(0.0);
+        let input = Self::_biased_to_raw(biased)?;
 
-        // generate a list of dac settings
-        let mut actives: Vec<(u16, u16, u16)> = Vec::new();
-        for (chan, v) in inp_chans {
-            actives.push((*chan as u16, vidx!(-v), vidx!(-v)));
-        }
-        for chan in out_chans {
-            actives.push((*chan as u16, zero, zero));
+        let chunk = self._read_biased_inner(&input, sense, None, false)?;
+
+        if ground {
+            let mut chans: Vec<usize> = biased.iter().map(|(c, _)| *c).collect();
+            chans.extend_from_slice(sense);
+            self.ground_slice_fast(&chans)?;
         }
-        let (mut upch, setdacs) = SetDAC::from_channels(&actives,
-            Some((zero,zero)), &ChannelState::VoltArb, &ChannelState::VoltArb)?;
-        // prepare OpAmps as all of them will connected to VoltArb
-        let mut chans_to_prep = out_chans.to_vec();
-        for (chan, _) in inp_chans {
-            chans_to_prep.push(*chan);
+
+        self._sender.send(Some(chunk))?;
+        Ok(self)
+    }
+
+    /// Perform a four-wire (Kelvin) resistance measurement
+    ///
+    /// This will apply `-vforce` to the `force_lo` channel and read the current flowing
+    /// through the `force_hi` channel, similar to [`Instrument::read_one`]. At the same
+    /// time the voltage drop across the device is measured on the `sense_hi` and
+    /// `sense_lo` channels, which are left floating (and therefore draw no current).
+    /// Both measurements are done in a single queued sequence while the device is
+    /// biased. The function returns the four-wire resistance, `(V(sense_hi) -
+    /// V(sense_lo))/I(force_hi)`, which excludes the resistance of the lines. This is
+    /// necessary for low resistance devices where the line resistance is comparable to
+    /// the resistance of the device.
+    ///
+    /// ```no_run
+    /// use libarc2::{Instrument};
+    /// # use libarc2::ArC2Error;
+    ///
+    /// # fn main() -> Result<(), ArC2Error> {
+    /// let mut arc2 = Instrument::open_with_fw(0, "fw.bin", true, true).unwrap();
+    ///
+    /// // Force through channels 17 (high) and 1 (low) and sense the
+    /// // voltage on channels 18 (high) and 2 (low).
+    /// let res = arc2.read_kelvin(17, 1, 18, 2, 0.1)?;
+    /// println!("Resistance: {} Ω", res);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn read_kelvin(&mut self, force_hi: usize, force_lo: usize, sense_hi: usize,
+        sense_lo: usize, vforce: f32) -> Result<f32, ArC2Error> {
+
+        if vforce.is_nan() {
+            return Err(ArC2Error::InvalidValue(vforce));
         }
-        self.amp_prep(Some(&chans_to_prep))?;
+
+        // Reset DAC configuration
+        self.reset_dacs()?;
+
+        // Sense channels must not drive the device
+        self.open_channels(&[sense_hi, sense_lo])?;
+
+        // Bias the force channels; force_hi is held at 0.0 V and
+        // current will be read from there
+        let input: Vec<(u16, u16, u16)> = vec![
+            (force_lo as u16, vidx!(-vforce), vidx!(-vforce)),
+            (force_hi as u16, vidx!(0.0), vidx!(0.0))];
+        let (mut upch, setdacs) = SetDAC::from_channels(&input, None,
+            &ChannelState::VoltArb, &ChannelState::Maintain)?;
+
+        self.amp_prep(Some(&[force_lo, force_hi]))?;
         self.process(upch.compile())?;
-        //
         for mut instr in setdacs {
             self.process(instr.compile())?;
         }
         self.process(&*UPDATE_DAC)?;
         self.add_delay(30_000u128)?;
 
-        // set all channels to Arbitrary Voltage
-        let mut channelconf = UpdateChannel::from_regs_global_state(ChannelState::VoltArb);
-        self.process(channelconf.compile())?;
+        let (mut vchunk, mut ichunk) = self._read_kelvin_inner(force_hi, sense_hi, sense_lo)?;
 
-        // Prepare the ADC mask for the readout
-        let mut adcmask = ChanMask::new();
+        // Withdraw voltage from the biasing channels
+        self.ground_all_fast()?.execute()?;
+        self.wait();
 
-        for chan in out_chans {
-            adcmask.set_enabled(*chan, true);
-        }
+        let voltages = self.read_chunk(&mut vchunk, &DataMode::All, &ReadType::Voltage)?;
+        let currents = self.read_chunk(&mut ichunk, &DataMode::All, &ReadType::Current)?;
 
-        let chunk = self.make_chunk()?;
+        Ok((voltages[sense_hi] - voltages[sense_lo])/currents[force_hi])
+    }
+
+    /// Perform a four-wire (Kelvin) resistance measurement under current forcing
+    ///
+    /// This is similar to [`Instrument::read_kelvin`] but instead of applying a voltage
+    /// a current of `current` is forced through the `force_hi` channel using the current
+    /// source (see [`Instrument::config_current_source`]) while `force_lo` is held at
+    /// 0.0 V. The voltage drop across the device is measured on the floating `sense_hi`
+    /// and `sense_lo` channels and the function returns `(V(sense_hi) -
+    /// V(sense_lo))/current`. The current source is disconnected after the measurement.
+    pub fn read_kelvin_current(&mut self, force_hi: usize, force_lo: usize, sense_hi: usize,
+        sense_lo: usize, current: f32) -> Result<f32, ArC2Error> {
+
+        // Reset DAC configuration
+        self.reset_dacs()?;
+
+        // Sense channels must not drive the device
+        self.open_channels(&[sense_hi, sense_lo])?;
+
+        // Static end of the device
+        self.config_channels(&[(force_lo as u16, 0.0)], None)?;
+
+        // Dynamic end of the device; this must be the last channel
+        // configuration before the measurement
+        self.config_current_source(&[force_hi], current, true, true)?;
+
+        let mut chunk = self._vread_channels_deferred_chunk(&[sense_hi, sense_lo], true)?;
+
+        // Release the current source and withdraw voltage from the biasing channels
+        self.connect_to_current_source(&[])?
+            .ground_all_fast()?
+            .execute()?;
+        self.wait();
+
+        let voltages = self.read_chunk(&mut chunk, &DataMode::All, &ReadType::Voltage)?;
+
+        Ok((voltages[sense_hi] - voltages[sense_lo])/current)
+    }
+
+    /// Queue the voltage read on the sense channels followed by the current read on
+    /// `force_hi` for a four-wire measurement. The device must be already biased. This
+    /// returns the voltage and current chunks respectively and it is guaranteed never
+    /// to flush the output.
+    fn _read_kelvin_inner(&mut self, force_hi: usize, sense_hi: usize, sense_lo: usize)
+        -> Result<(Chunk, Chunk), ArC2Error> {
+
+        // Voltage across the device; use averaging as four-wire
+        // measurements are typically done on low-resistance devices
+        let vchunk = self._vread_channels_deferred_chunk(&[sense_hi, sense_lo], true)?;
+
+        // Current through the device
+        let adcmask = ChanMask::from_channels(&[force_hi]);
+        let ichunk = self.make_chunk()?;
 
         #[cfg(feature="zero_before_write")]
-        match self._zero_chunk(&chunk) {
+        match self._zero_chunk(&ichunk) {
             Ok(()) => {},
-            Err(err) => { eprintln!("Zeroing chunk at {} failed: {}", chunk.addr(), err) }
+            Err(err) => { eprintln!("Zeroing chunk at {} failed: {}", ichunk.addr(), err) }
         };
 
-        let mut currentread = CurrentRead::new(&adcmask, chunk.addr(),
-            chunk.flag_addr(), VALUEAVAILFLAG);
+        let mut currentread = CurrentRead::new(&adcmask, ichunk.addr(),
+            ichunk.flag_addr(), VALUEAVAILFLAG);
         self.process(currentread.compile())?;
         self.add_delay(1_000u128)?;
 
-        // Similar to 'read_slice_open', need to force an amp prep
-        // on the C READ channels to avoid spikes during range
-        // transitions.
+        // Force an AMP PRP on the C READ channel to avoid spikes during
+        // range transitions; see `_read_biased_inner`.
         let mut amp_prep = AmpPrep::new(&adcmask);
         self.process(amp_prep.compile())?;
 
-        Ok(chunk)
+        Ok((vchunk, ichunk))
     }
 
+    /// Convert a list of `(channel, voltage)` tuples into `(channel, raw DAC voltage)`
+    /// tuples, raising an error if any of the voltages is not a number.
+    fn _biased_to_raw(biased: &[(usize, f32)]) -> Result<Vec<(usize, u16)>, ArC2Error> {
 
-    /// Read all the available crosspoints at the specified voltage
-    ///
-    /// This function will read all available crosspoints on the array. This can be done
-    /// either by high biasing the rows ([`BiasOrder::Rows`]) or columns ([`BiasOrder::Columns`]).
-    /// The result is stored in linear vector (and not a 2D matrix) in blocks of 32 values
-    /// in channel order. When order is `Columns` the low potential channels are `[16..32)`
+        let mut input: Vec<(usize, u16)> = Vec::with_capacity(biased.len());
+
+        for (chan, v) in biased {
+            if v.is_nan() {
+                return Err(ArC2Error::InvalidValue(*v));
+            }
+            input.push((*chan, vidx!(*v)));
+        }
+
+        Ok(input)
+    }
+
+    /// MAC operation with given input and output channels and input voltages
+    ///
+    /// This function will perform multiply accumulation operation. Input channel list contains
+    /// input channels and corresponding input voltage. Then, the currents from output channels
+    /// will be collected.
+    pub fn mac(&mut self, inp_chans: &[(usize, f32)], out_chans: &[usize]) -> Result<Vec<f32>, ArC2Error> {
+
+        // let mut results = Vec::with_capacity(out_chans.len());
+        let mut res : Vec<f32>;
+        let mut chunk: Chunk;
+
+        // Reset DAC configuration
+        self.reset_dacs()?;
+
+        chunk = self._mac_inner(inp_chans, out_chans)?;
+
+        // Withdraw voltage from the biasing channels
+        self.ground_all_fast()?.execute()?;
+        self.wait();
+
+        res = self.read_chunk(&mut chunk, &DataMode::All, &ReadType::Current)?;
+
+        // For each channel in `out_chans`, get the corresponding value from `res`
+        let values = out_chans.iter().map(|&chan| res[chan]).collect();
+
+        Ok(values)
+    }
+
+    /// Batched MAC operation with given input and output channels
+    ///
+    /// This is similar to [`Instrument::mac`] but performs a MAC operation for every input
+    /// vector in `inputs`. Each vector must contain one voltage for each of the channels in
+    /// `inp_chans`, in the same order. All operations are queued in a single program and
+    /// executed at once, which avoids the overhead of synchronising with the instrument for
+    /// every vector. The function returns an N×M matrix (as a vector of rows) with the
+    /// currents of the M `out_chans` for each of the N input vectors.
+    ///
+    /// ```no_run
+    /// use libarc2::{Instrument};
+    /// # use libarc2::ArC2Error;
+    ///
+    /// # fn main() -> Result<(), ArC2Error> {
+    /// let mut arc2 = Instrument::open_with_fw(0, "fw.bin", true, true).unwrap();
+    ///
+    /// let inputs = vec![vec![0.1, 0.2, 0.0], vec![0.0, 0.1, 0.2]];
+    /// let outputs = arc2.mac_batch(&inputs, &[16, 17, 18], &[0, 1])?;
+    /// assert_eq!(outputs.len(), 2);
+    /// assert_eq!(outputs[0].len(), 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn mac_batch(&mut self, inputs: &[Vec<f32>], inp_chans: &[usize], out_chans: &[usize])
+        -> Result<Vec<Vec<f32>>, ArC2Error> {
+
+        for input in inputs {
+            if input.len() != inp_chans.len() {
+                return Err(ArC2Error::DimensionError(input.len(), inp_chans.len()));
+            }
+        }
+
+        self.reset_dacs()?;
+
+        let mut chunks: Vec<Chunk> = Vec::with_capacity(inputs.len());
+        for input in inputs {
+            let pairs: Vec<(usize, f32)> = inp_chans.iter()
+                .zip(input.iter())
+                .map(|(c, v)| (*c, *v))
+                .collect();
+            chunks.push(self._mac_inner(&pairs, out_chans)?);
+            self.ground_all_fast()?;
+        }
+
+        self.execute()?;
+        self.wait();
+
+        let mut result: Vec<Vec<f32>> = Vec::with_capacity(inputs.len());
+        for mut chunk in chunks {
+            let res = self.read_chunk(&mut chunk, &DataMode::All, &ReadType::Current)?;
+            result.push(out_chans.iter().map(|&chan| res[chan]).collect());
+        }
+
+        Ok(result)
+    }
+
+    /// Signed MAC operation using differential output pairs
+    ///
+    /// This is similar to [`Instrument::mac`] but every logical output is formed by a pair
+    /// of `(positive, negative)` channels in `out_pairs` and its value is the difference of
+    /// their currents, I⁺ − I⁻, which allows for signed weights. If `gref` is provided it
+    /// should contain one reference conductance per pair and the differences will be
+    /// divided by it to convert them to weight units, assuming weights were mapped with
+    /// [`signed_weight_targets`] using the same reference conductance.
+    ///
+    /// ```no_run
+    /// use libarc2::{Instrument};
+    /// # use libarc2::ArC2Error;
+    ///
+    /// # fn main() -> Result<(), ArC2Error> {
+    /// let mut arc2 = Instrument::open_with_fw(0, "fw.bin", true, true).unwrap();
+    ///
+    /// // Two signed outputs from channel pairs (0, 1) and (2, 3)
+    /// let out = arc2.mac_differential(&[(16, 0.1), (17, 0.2)], &[(0, 1), (2, 3)],
+    ///     Some(&[1e-4, 1e-4]))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn mac_differential(&mut self, inp_chans: &[(usize, f32)], out_pairs: &[(usize, usize)],
+        gref: Option<&[f32]>) -> Result<Vec<f32>, ArC2Error> {
+
+        if let Some(g) = gref {
+            if g.len() != out_pairs.len() {
+                return Err(ArC2Error::DimensionError(g.len(), out_pairs.len()));
+            }
+        }
+
+        let mut out_chans: Vec<usize> = Vec::with_capacity(2*out_pairs.len());
+        for (pos, neg) in out_pairs {
+            out_chans.push(*pos);
+            out_chans.push(*neg);
+        }
+
+        let currents = self.mac(inp_chans, &out_chans)?;
+
+        Ok(currents.chunks(2)
+            .enumerate()
+            .map(|(idx, pair)| {
+                let diff = pair[0] - pair[1];
+                match gref {
+                    Some(g) => diff / g[idx],
+                    None => diff
+                }
+            })
+            .collect())
+    }
+
+    /// MAC operation with encoded inputs
+    ///
+    /// This is similar to [`Instrument::mac`] but input values are applied according to
+    /// `encoding`; see [`MACEncoding`] for the available schemes and the range of input
+    /// values each one expects. For encodings that require multiple reads all of them
+    /// are queued in a single program and the recombined currents of `out_chans` are
+    /// returned.
+    ///
+    /// ```no_run
+    /// use libarc2::{Instrument, MACEncoding};
+    /// # use libarc2::ArC2Error;
+    ///
+    /// # fn main() -> Result<(), ArC2Error> {
+    /// let mut arc2 = Instrument::open_with_fw(0, "fw.bin", true, true).unwrap();
+    ///
+    /// // 8-bit signed inputs applied at 0.2 V
+    /// let out = arc2.mac_encoded(&[(16, 0.5), (17, -0.25)], &[0, 1],
+    ///     MACEncoding::BitSerial(0.2, 8))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn mac_encoded(&mut self, inp_chans: &[(usize, f32)], out_chans: &[usize],
+        encoding: MACEncoding) -> Result<Vec<f32>, ArC2Error> {
+
+        for (_, v) in inp_chans {
+            if v.is_nan() {
+                return Err(ArC2Error::InvalidValue(*v));
+            }
+        }
+
+        // Every plane is a set of input voltages along with the weight
+        // of its readout in the final result
+        let planes: Vec<(Vec<(usize, f32)>, f32)> = match encoding {
+            MACEncoding::Amplitude(vmax) => {
+                let vmax = vmax.abs();
+                let clipped = inp_chans.iter()
+                    .map(|(c, v)| (*c, v.max(-vmax).min(vmax)))
+                    .collect();
+                vec![(clipped, 1.0)]
+            },
+            MACEncoding::PulseWidth(amplitude, slots) => {
+                if slots == 0 {
+                    return Err(ArC2Error::InvalidValue(0.0));
+                }
+                // number of slots each input is active for
+                let active: Vec<(usize, f32, usize)> = inp_chans.iter()
+                    .map(|(c, v)| {
+                        let x = v.max(-1.0).min(1.0);
+                        (*c, x.signum(), (x.abs() * slots as f32).round() as usize)
+                    })
+                    .collect();
+                (0..slots).map(|slot| {
+                    let plane = active.iter()
+                        .map(|(c, sign, n)| (*c, if slot < *n { sign * amplitude } else { 0.0 }))
+                        .collect();
+                    (plane, 1.0 / slots as f32)
+                }).collect()
+            },
+            MACEncoding::BitSerial(amplitude, bits) => {
+                if bits == 0 || bits > 16 {
+                    return Err(ArC2Error::InvalidValue(bits as f32));
+                }
+                let full = ((1u32 << bits) - 1) as f32;
+                let quantised: Vec<(usize, f32, u32)> = inp_chans.iter()
+                    .map(|(c, v)| {
+                        let x = v.max(-1.0).min(1.0);
+                        (*c, x.signum(), (x.abs() * full).round() as u32)
+                    })
+                    .collect();
+                (0..bits).map(|bit| {
+                    let plane = quantised.iter()
+                        .map(|(c, sign, q)| (*c, if (q >> bit) & 1 == 1 { sign * amplitude } else { 0.0 }))
+                        .collect();
+                    (plane, (1u32 << bit) as f32 / full)
+                }).collect()
+            }
+        };
+
+        self.reset_dacs()?;
+
+        let mut chunks: Vec<(Chunk, f32)> = Vec::with_capacity(planes.len());
+        for (plane, weight) in planes {
+            chunks.push((self._mac_inner(&plane, out_chans)?, weight));
+            self.ground_all_fast()?;
+        }
+
+        self.execute()?;
+        self.wait();
+
+        let mut result = vec![0.0f32; out_chans.len()];
+        for (mut chunk, weight) in chunks {
+            let res = self.read_chunk(&mut chunk, &DataMode::All, &ReadType::Current)?;
+            for (idx, chan) in out_chans.iter().enumerate() {
+                result[idx] += weight * res[*chan];
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Tiled MAC operation for weight matrices larger than the array
+    ///
+    /// This will compute `y = W·x` for a logical weight matrix that has been partitioned
+    /// into `tiles` (see [`MACTile`] and [`tile_layout`]). Each tile is evaluated with
+    /// [`Instrument::mac`] using the part of `x` corresponding to its inputs and the
+    /// partial sums are accumulated on the host. Tiles are evaluated one slot at a time,
+    /// in order of first appearance of each slot in `tiles`. Before the tiles of a slot
+    /// are evaluated `prepare` is called with all of them so that the array can be
+    /// reprogrammed, for instance with [`Instrument::program_matrix`] and
+    /// [`MACTile::sub_matrix`]. The length of the result is the largest output index of
+    /// all tiles.
+    ///
+    /// ```no_run
+    /// use libarc2::{Instrument, tile_layout};
+    /// # use libarc2::ArC2Error;
+    ///
+    /// # fn main() -> Result<(), ArC2Error> {
+    /// let mut arc2 = Instrument::open_with_fw(0, "fw.bin", true, true).unwrap();
+    ///
+    /// // 64×48 conductance matrix on a 16×16 corner of the array
+    /// let weights = vec![vec![1e-5f32; 48]; 64];
+    /// let tiles = tile_layout(64, 48, &(0..16).collect::<Vec<usize>>(),
+    ///     &(16..32).collect::<Vec<usize>>());
+    /// for tile in &tiles {
+    ///     println!("{:?}", tile);
+    /// }
+    ///
+    /// let x = vec![0.1f32; 48];
+    /// let y = arc2.mac_tiled(&tiles, &x, |arc2, slot| {
+    ///     for tile in slot {
+    ///         arc2.program_matrix(&tile.sub_matrix(&weights), &tile.inp_chans,
+    ///             &tile.out_chans, 0.05, 0.05, 0.2, (0.8, 2.0), (-0.8, -2.5), 1_000, 8,
+    ///             50, 3)?;
+    ///     }
+    ///     Ok(())
+    /// })?;
+    /// assert_eq!(y.len(), 64);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn mac_tiled<F>(&mut self, tiles: &[MACTile], x: &[f32], mut prepare: F)
+        -> Result<Vec<f32>, ArC2Error>
+        where F: FnMut(&mut Instrument, &[MACTile]) -> Result<(), ArC2Error> {
+
+        let m = tiles.iter().map(|t| t.outputs.end).max().unwrap_or(0);
+        let n = tiles.iter().map(|t| t.inputs.end).max().unwrap_or(0);
+
+        if x.len() != n {
+            return Err(ArC2Error::DimensionError(x.len(), n));
+        }
+
+        for tile in tiles {
+            if tile.inp_chans.len() != tile.inputs.len() {
+                return Err(ArC2Error::DimensionError(tile.inp_chans.len(), tile.inputs.len()));
+            }
+            if tile.out_chans.len() != tile.outputs.len() {
+                return Err(ArC2Error::DimensionError(tile.out_chans.len(), tile.outputs.len()));
+            }
+        }
+
+        let mut slots: Vec<usize> = Vec::new();
+        for tile in tiles {
+            if !slots.contains(&tile.slot) {
+                slots.push(tile.slot);
+            }
+        }
+
+        let mut y = vec![0.0f32; m];
+
+        for slot in slots {
+
+            let group: Vec<MACTile> = tiles.iter()
+                .filter(|t| t.slot == slot)
+                .cloned()
+                .collect();
+            prepare(self, &group)?;
+
+            for tile in &group {
+
+                let inputs: Vec<(usize, f32)> = tile.inp_chans.iter()
+                    .zip(&x[tile.inputs.clone()])
+                    .map(|(c, v)| (*c, *v))
+                    .collect();
+
+                let partial = self.mac(&inputs, &tile.out_chans)?;
+                for (o, value) in tile.outputs.clone().zip(partial) {
+                    y[o] += value;
+                }
+            }
+        }
+
+        Ok(y)
+    }
+
+    /// Calibrate MAC operations on a programmed array
+    ///
+    /// This will apply the known input vectors in `inputs` (one voltage per channel in
+    /// `inp_chans`) through [`Instrument::mac_batch`] on an array programmed with the
+    /// conductances in `weights` (one row per input channel, one column per output channel,
+    /// as with [`Instrument::program_matrix`]) and compare the raw results with the ideal
+    /// ones, `Σᵢ Vᵢ·Gᵢⱼ`. A per-output gain and offset is fitted with least squares and,
+    /// if `full` is `true`, a linear correction matrix is fitted on top of that to account
+    /// for crosstalk between outputs. The latter requires at least as many input vectors
+    /// as output channels; for best results the input vectors should be linearly
+    /// independent. Use [`Instrument::mac_calibrated`] or [`MACCalibration::apply`] to
+    /// correct subsequent results.
+    ///
+    /// ```no_run
+    /// use libarc2::{Instrument};
+    /// # use libarc2::ArC2Error;
+    ///
+    /// # fn main() -> Result<(), ArC2Error> {
+    /// let mut arc2 = Instrument::open_with_fw(0, "fw.bin", true, true).unwrap();
+    ///
+    /// let weights = vec![vec![1e-4, 5e-5], vec![2e-5, 1e-5]];
+    /// let inputs = vec![vec![0.1, 0.0], vec![0.0, 0.1], vec![0.1, 0.1], vec![0.2, 0.05]];
+    /// let cal = arc2.calibrate_mac(&[0, 1], &[16, 17], &weights, &inputs, true)?;
+    ///
+    /// // store for later use
+    /// std::fs::write("mac.cal", cal.to_string()).unwrap();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn calibrate_mac(&mut self, inp_chans: &[usize], out_chans: &[usize],
+        weights: &[Vec<f32>], inputs: &[Vec<f32>], full: bool)
+        -> Result<MACCalibration, ArC2Error> {
+
+        if weights.len() != inp_chans.len() {
+            return Err(ArC2Error::DimensionError(weights.len(), inp_chans.len()));
+        }
+        for row in weights {
+            if row.len() != out_chans.len() {
+                return Err(ArC2Error::DimensionError(row.len(), out_chans.len()));
+            }
+        }
+        if inputs.len() < 2 {
+            return Err(ArC2Error::DimensionError(inputs.len(), 2));
+        }
+        if full && inputs.len() < out_chans.len() {
+            return Err(ArC2Error::DimensionError(inputs.len(), out_chans.len()));
+        }
+
+        let measured = self.mac_batch(inputs, inp_chans, out_chans)?;
+
+        // ideal results for every input vector
+        let expected: Vec<Vec<f64>> = inputs.iter()
+            .map(|x| (0..out_chans.len())
+                .map(|j| x.iter().zip(weights).map(|(v, row)| (*v as f64) * (row[j] as f64)).sum())
+                .collect())
+            .collect();
+
+        let n = inputs.len() as f64;
+        let mut gain: Vec<f32> = Vec::with_capacity(out_chans.len());
+        let mut offset: Vec<f32> = Vec::with_capacity(out_chans.len());
+
+        for j in 0..out_chans.len() {
+            let mean_m: f64 = measured.iter().map(|m| m[j] as f64).sum::<f64>() / n;
+            let mean_e: f64 = expected.iter().map(|e| e[j]).sum::<f64>() / n;
+            let mut cov = 0.0f64;
+            let mut var = 0.0f64;
+            for (m, e) in measured.iter().zip(&expected) {
+                cov += (m[j] as f64 - mean_m) * (e[j] - mean_e);
+                var += (m[j] as f64 - mean_m).powi(2);
+            }
+            if var == 0.0 || var.is_nan() {
+                return Err(ArC2Error::CalibrationError(
+                    format!("output {} does not respond to inputs", out_chans[j])));
+            }
+            let g = cov / var;
+            gain.push(g as f32);
+            offset.push((mean_e - g * mean_m) as f32);
+        }
+
+        let mut cal = MACCalibration { gain, offset, correction: None };
+
+        if full {
+            // least squares fit of every row of the correction matrix using
+            // the normal equations CᵀC·a = Cᵀe
+            let corrected: Vec<Vec<f64>> = measured.iter()
+                .map(|m| cal.apply(m).iter().map(|v| *v as f64).collect())
+                .collect();
+            let size = out_chans.len();
+
+            let mut ctc = vec![vec![0.0f64; size]; size];
+            for c in &corrected {
+                for r in 0..size {
+                    for k in 0..size {
+                        ctc[r][k] += c[r] * c[k];
+                    }
+                }
+            }
+
+            let mut correction: Vec<Vec<f32>> = Vec::with_capacity(size);
+            for j in 0..size {
+                let cte: Vec<f64> = (0..size)
+                    .map(|r| corrected.iter().zip(&expected).map(|(c, e)| c[r] * e[j]).sum())
+                    .collect();
+                match _solve_linear(ctc.clone(), cte) {
+                    Some(row) => correction.push(row.iter().map(|v| *v as f32).collect()),
+                    None => {
+                        return Err(ArC2Error::CalibrationError(
+                            "input vectors are not linearly independent".to_string()));
+                    }
+                }
+            }
+
+            cal.correction = Some(correction);
+        }
+
+        Ok(cal)
+    }
+
+    /// MAC operation with calibration
+    ///
+    /// This is the same as [`Instrument::mac`] but results are corrected with `cal`, as
+    /// produced by [`Instrument::calibrate_mac`] for the same channels.
+    pub fn mac_calibrated(&mut self, inp_chans: &[(usize, f32)], out_chans: &[usize],
+        cal: &MACCalibration) -> Result<Vec<f32>, ArC2Error> {
+
+        if cal.gain.len() != out_chans.len() {
+            return Err(ArC2Error::DimensionError(out_chans.len(), cal.gain.len()));
+        }
+
+        let measured = self.mac(inp_chans, out_chans)?;
+
+        Ok(cal.apply(&measured))
+    }
+
+    /// Queue a MAC operation and return the chunk holding the output currents.
+    /// This function is guaranteed never to flush the output.
+    fn _mac_inner(&mut self, inp_chans: &[(usize, f32)], out_chans: &[usize]) -> Result<Chunk, ArC2Error> {
+
+        let zero: u16 = vidx!(0.0);
+
+        // generate a list of dac settings
+        let mut actives: Vec<(u16, u16, u16)> = Vec::new();
+        for (chan, v) in inp_chans {
+            actives.push((*chan as u16, vidx!(-v), vidx!(-v)));
+        }
+        for chan in out_chans {
+            actives.push((*chan as u16, zero, zero));
+        }
+        let (mut upch, setdacs) = SetDAC::from_channels(&actives,
+            Some((zero,zero)), &ChannelState::VoltArb, &ChannelState::VoltArb)?;
+        // prepare OpAmps as all of them will connected to VoltArb
+        let mut chans_to_prep = out_chans.to_vec();
+        for (chan, _) in inp_chans {
+            chans_to_prep.push(*chan);
+        }
+        self.amp_prep(Some(&chans_to_prep))?;
+        self.process(upch.compile())?;
+        //
+        for mut instr in setdacs {
+            self.process(instr.compile())?;
+        }
+        self.process(&*UPDATE_DAC)?;
+        self.add_delay(30_000u128)?;
+
+        // set all channels to Arbitrary Voltage
+        let mut channelconf = UpdateChannel::from_regs_global_state(ChannelState::VoltArb);
+        self.process(channelconf.compile())?;
+
+        // Prepare the ADC mask for the readout
+        let mut adcmask = ChanMask::new();
+
+        for chan in out_chans {
+            adcmask.set_enabled(*chan, true);
+        }
+
+        let chunk = self.make_chunk()?;
+
+        #[cfg(feature="zero_before_write")]
+        match self._zero_chunk(&chunk) {
+            Ok(()) => {},
+            Err(err) => { eprintln!("Zeroing chunk at {} failed: {}", chunk.addr(), err) }
+        };
+
+        let mut currentread = CurrentRead::new(&adcmask, chunk.addr(),
+            chunk.flag_addr(), VALUEAVAILFLAG);
+        self.process(currentread.compile())?;
+        self.add_delay(1_000u128)?;
+
+        // Similar to 'read_slice_open', need to force an amp prep
+        // on the C READ channels to avoid spikes during range
+        // transitions.
+        let mut amp_prep = AmpPrep::new(&adcmask);
+        self.process(amp_prep.compile())?;
+
+        Ok(chunk)
+    }
+
+
+    /// Read all the available crosspoints at the specified voltage
+    ///
+    /// This function will read all available crosspoints on the array. This can be done
+    /// either by high biasing the rows ([`BiasOrder::Rows`]) or columns ([`BiasOrder::Columns`]).
+    /// The result is stored in linear vector (and not a 2D matrix) in blocks of 32 values
+    /// in channel order. When order is `Columns` the low potential channels are `[16..32)`
     /// and `[48..64)` (wordlines). When order is `Rows` the low potential channels are
     /// `[0..16)` and `[32..48)` (bitlines). Function [`Instrument::read_slice()`] is
     /// applied for every one of the selected channels. This function implies the configuration
@@ -1804,111 +3570,1004 @@ impl Instrument {
     /// fashion use [`Instrument::read_slice_open`].
     pub fn read_all(&mut self, vread: f32, order: BiasOrder) -> Result<Vec<f32>, ArC2Error> {
 
-        let mut results = Vec::with_capacity(32*32);
+        let mut results = Vec::with_capacity(32*32);
+
+        let bias_channels = match order {
+            BiasOrder::Rows => &*ALL_WORDS,
+            BiasOrder::Columns => &*ALL_BITS
+        };
+
+        for chan in bias_channels {
+            results.append(&mut self.read_slice(*chan, vread)?);
+        }
+
+        Ok(results)
+
+    }
+
+    /// Read all the values which have `chan` as the low potential channel while
+    /// biasing the unselected lines according to the specified [`BiasScheme`].
+    ///
+    /// Semantics of `chan` and `vread` are the same as in [`Instrument::read_slice`]. As
+    /// all high channels of the slice are read simultaneously, unselected lines are all
+    /// the lines of the same type as `chan`. Using [`BiasScheme::Grounded`] is equivalent
+    /// to [`Instrument::read_slice`].
+    pub fn read_slice_with_scheme(&mut self, chan: usize, vread: f32, scheme: BiasScheme)
+        -> Result<Vec<f32>, ArC2Error> {
+
+        // Reset DAC configuration
+        self.reset_dacs()?;
+
+        let (channels, mode) = if (chan < 16) || ((32 <= chan) && (chan < 48)) {
+            (&*ALL_WORDS, DataMode::Words)
+        } else {
+            (&*ALL_BITS, DataMode::Bits)
+        };
+
+        // Initiate a read operation get the address of the data to be...
+        let mut chunk = self._read_slice_scheme_inner(chan, channels, vread, &scheme)?;
+        // ... and finally withdraw voltage from the biasing channels
+        self.ground_all_fast()?.execute()?;
+        self.wait();
+
+        self.read_chunk(&mut chunk, &mode, &ReadType::Current)
+    }
+
+    /// Read all the available crosspoints at the specified voltage while biasing the
+    /// unselected lines according to the specified [`BiasScheme`]. This follows the
+    /// same conventions as [`Instrument::read_all`] but
+    /// [`Instrument::read_slice_with_scheme`] is applied for every one of the selected
+    /// channels instead.
+    pub fn read_all_with_scheme(&mut self, vread: f32, order: BiasOrder, scheme: BiasScheme)
+        -> Result<Vec<f32>, ArC2Error> {
+
+        let mut results = Vec::with_capacity(32*32);
+
+        let bias_channels = match order {
+            BiasOrder::Rows => &*ALL_WORDS,
+            BiasOrder::Columns => &*ALL_BITS
+        };
+
+        for chan in bias_channels {
+            results.append(&mut self.read_slice_with_scheme(*chan, vread, scheme)?);
+        }
+
+        Ok(results)
+    }
+
+    /// Common read functionality for array reads with a [`BiasScheme`]. Channel `low`
+    /// is biased at `-vread`, `highs` are read at 0.0 V and everything else is biased
+    /// according to `scheme`. This function is guaranteed never to flush the output.
+    fn _read_slice_scheme_inner(&mut self, low: usize, highs: &[usize], vread: f32,
+        scheme: &BiasScheme) -> Result<Chunk, ArC2Error> {
+
+        let (same, opposite) = _array_unselected(low, highs);
+
+        match scheme.levels(-vread, 0.0) {
+            Some((vsame, vopposite)) => {
+                // Unselected lines of the same type are set via the base voltage
+                // and the unselected opposite lines explicitly
+                let mut biased: Vec<(usize, u16)> = Vec::with_capacity(1 + opposite.len());
+                biased.push((low, vidx!(-vread)));
+                for c in opposite {
+                    biased.push((c, vidx!(vopposite)));
+                }
+                self._read_biased_inner(&biased, highs, Some(vidx!(vsame)), false)
+            },
+            None => {
+                let mut unselected = same;
+                unselected.extend(opposite);
+                self.open_channels(&unselected)?;
+                self._read_biased_inner(&[(low, vidx!(-vread))], highs, None, true)
+            }
+        }
+    }
+
+    fn _vread_channels_deferred_chunk(&mut self, chans: &[usize], avg: bool) -> Result<Chunk, ArC2Error> {
+
+        // Create a new mask and populate it with the specified channels
+        let mut mask = ChanMask::none();
+
+        for c in chans {
+            mask.set_enabled(*c, true);
+        }
+
+        let chunk = self.make_chunk()?;
+
+        let mut voltageread = VoltageRead::new(&mask, avg, chunk.addr(),
+            chunk.flag_addr(), VALUEAVAILFLAG);
+        self.process(voltageread.compile())?;
+
+        Ok(chunk)
+
+    }
+
+    /// Do a voltage read on all the specified channels.
+    /// This function will read the voltage on all specified channels and return their
+    /// values in ascending channel order. Set `avg` to `true` to enable averaging
+    /// but that will increase readout time from 10 to 320 μs. Calling this function
+    /// will immediately flush the FIFO and return the value.
+    pub fn vread_channels(&mut self, uchans: &[usize], avg: bool) -> Result<Vec<f32>, ArC2Error> {
+
+        let mut chans = uchans.to_vec();
+        chans.sort();
+
+        let mut chunk = self._vread_channels_deferred_chunk(&chans, avg)?;
+        self.execute()?;
+        self.wait();
+        let mut results: Vec<f32> = Vec::with_capacity(chans.len());
+
+        let res = self.read_chunk(&mut chunk, &DataMode::All, &ReadType::Voltage)?;
+
+        for c in &chans {
+            results.push(res[*c]);
+        }
+
+        Ok(results)
+
+    }
+
+    /// Do a voltage read on the specified channels. Unlike [`Instrument::vread_channels`]
+    /// execution of this command is deferred, which means that it needs to be followed
+    /// by an [`Instrument::execute`] call (at some point). As the equivalent current
+    /// function this is meant to be used in a chain of commands without immediately
+    /// flushing the FIFO. If you want to bypass the command queue and get an immediate
+    /// voltage read use [`Instrument::vread_channels`] instead.
+    pub fn vread_channels_deferred(&mut self, uchans: &[usize], avg: bool) -> Result<&mut Self, ArC2Error> {
+
+        let mut chans = uchans.to_vec();
+        chans.sort();
+
+        let chunk = self._vread_channels_deferred_chunk(&chans, avg)?;
+        self._sender.send(Some(chunk))?;
+        Ok(self)
+
+    }
+
+    /// Connect selected channels to the current source targeting a specific
+    /// current. The series resistor (or digipot) of the current source is selected
+    /// with [`SourceConf::for_current`][`crate::registers::SourceConf::for_current`]
+    /// and CREF is placed at the ±10 V rail (depending on the polarity of `current`)
+    /// to maximise headroom. CSET is then set to the voltage drop required to produce
+    /// the selected current through the series resistor. Setting `preset_range` to
+    /// `true` will generate extra instructions to reconfigure the source to the
+    /// necessary range (series resistor) for the selected current. This can be set to
+    /// `false` only if a previous call has already selected the same series resistor.
+    /// Setting `float` to `true` will also disconnect the DACs of the selected channels
+    /// so that they don't interfere with the current source. Obviously connecting more
+    /// than one channels to the current source will split (probably unevenly) the
+    /// current between the connected DUTs. Please note that configuring the source only
+    /// affects the dynamic end of the connection (where current is sourced/sinked
+    /// from/to). The static end of any DUT must be configured separately. Also note
+    /// that any subsequent channel configuration will revert the source configuration
+    /// to its default state, so this should typically be the last channel configuration
+    /// before a measurement. Use [`Instrument::connect_to_current_source`] with an
+    /// empty channel list to disconnect the current source.
+    pub fn config_current_source(&mut self, chans: &[usize], current: f32,
+        float: bool, preset_range: bool) -> Result<&mut Self, ArC2Error> {
+
+        if current.is_nan() || current == 0.0 {
+            return Err(ArC2Error::InvalidValue(current))
+        }
+
+        // Find the necessary resistor and voltage drop for the requested
+        // current; if there's none the current is out of range
+        let (sourceconf, vdrop) = match SourceConf::for_current(current) {
+            Some(conf) => conf,
+            None => { return Err(ArC2Error::InvalidValue(current)) }
+        };
+
+        // The source references cannot support more than 1.0 V across the
+        // series resistor; anything else means the configuration is bogus
+        if vdrop <= 0.0 || vdrop > 1.0 {
+            return Err(ArC2Error::InvalidValue(current))
+        }
+
+        // CREF at the rail for maximum headroom and CSET towards 0 V so
+        // that the difference between the two dictates the current
+        let cref = current.signum()*10.0;
+        let cset = cref - current.signum()*vdrop;
+        self.config_aux_channels(&[(AuxDACFn::CSET, cset), (AuxDACFn::CREF, cref)])?;
+
+        if preset_range || float {
+            let mut chanconf = ChannelConf::new();
+            if float {
+                // ensure that channels connected to the current source
+                // are floating
+                for c in chans {
+                    chanconf.set(*c, ChannelState::Open);
+                    self._tia_state.set_enabled(*c, true);
+                }
+            }
+            let mut upch = UpdateChannel::from_regs(&sourceconf, &chanconf);
+            self.process(upch.compile())?;
+        }
+
+        self.connect_to_current_source(chans)?;
+        self.add_delay(30_000u128)?;
+
+        Ok(self)
+    }
+
+    /// Force a current through the selected channels and optionally measure the
+    /// resulting voltage on them.
+    ///
+    /// The current source is configured as in [`Instrument::config_current_source`]
+    /// with the DACs of the channels floating. If `vread` is `true` a voltage read
+    /// will be performed on all `chans` after a settling time of `settle_nanos` and
+    /// the voltages will be returned in ascending channel order. This function will
+    /// flush the command buffer. Current will keep flowing after this function
+    /// returns; use [`Instrument::connect_to_current_source`] with an empty channel
+    /// list or [`Instrument::ground_all`] to stop it. As with
+    /// [`Instrument::config_current_source`] the static end of the DUTs must be
+    /// configured beforehand.
+    ///
+    /// ```no_run
+    /// use libarc2::{Instrument};
+    /// # use libarc2::ArC2Error;
+    ///
+    /// # fn main() -> Result<(), ArC2Error> {
+    /// let mut arc2 = Instrument::open_with_fw(0, "fw.bin", true, true).unwrap();
+    ///
+    /// // Hold channel 3 at 0.0 V and force 10 μA through channel 19
+    /// arc2.config_channels(&[(3, 0.0)], None)?;
+    /// let v = arc2.force_current(&[19], 10e-6, 100_000u128, true)?.unwrap();
+    /// println!("Voltage: {} V", v[0]);
+    /// arc2.connect_to_current_source(&[])?.ground_all()?.execute()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn force_current(&mut self, chans: &[usize], current: f32, settle_nanos: u128,
+        vread: bool) -> Result<Option<Vec<f32>>, ArC2Error> {
+
+        self.config_current_source(chans, current, true, true)?;
+
+        if settle_nanos > 0u128 {
+            self.add_delay(settle_nanos)?;
+        }
+
+        if !vread {
+            self.execute()?;
+            return Ok(None);
+        }
+
+        let mut sorted = chans.to_vec();
+        sorted.sort();
+
+        let mut chunk = self._vread_channels_deferred_chunk(&sorted, true)?;
+        self.execute()?;
+        self.wait();
+
+        let res = self.read_chunk(&mut chunk, &DataMode::All, &ReadType::Voltage)?;
+
+        Ok(Some(sorted.iter().map(|c| res[*c]).collect()))
+    }
+
+    /// Program a crosspoint to a target resistance
+    ///
+    /// This is a closed-loop incremental step pulse programming routine that alternates
+    /// pulses and reads on the crosspoint defined by `low` and `high` until its resistance
+    /// is within `tolerance` (relative, 0.05 for 5%) of `target` or `budget` pulses have
+    /// been applied. Pulses follow the conventions of [`Instrument::pulse_one`] and reads
+    /// are done at `vread` as with [`Instrument::read_one`]. When the resistance is
+    /// higher than the target pulses from the `vset` window `(start, max)` are used and
+    /// when lower pulses from the `vreset` window are used, so the two would typically be
+    /// of opposite polarity. Polarity therefore switches automatically whenever the
+    /// target is overshot. How the amplitude evolves between `start` and `max` is governed
+    /// by `policy` (see [`ProgramPolicy`]). Once the amplitude has reached `max` and the
+    /// device is still not in tolerance the pulse width is doubled after every pulse,
+    /// starting from `pw_nanos.0` and up to `pw_nanos.1`.
+    ///
+    /// The function returns whether the target was reached along with the full trajectory
+    /// as a list of `(voltage, pulse width, resistance)` tuples. The first entry is the
+    /// initial read of the device with a 0.0 V, 0 ns pulse.
+    ///
+    /// ```no_run
+    /// use libarc2::{Instrument, ProgramPolicy};
+    /// # use libarc2::ArC2Error;
+    ///
+    /// # fn main() -> Result<(), ArC2Error> {
+    /// let mut arc2 = Instrument::open_with_fw(0, "fw.bin", true, true).unwrap();
+    ///
+    /// // Program crosspoint 3 × 19 to 10 kΩ ± 5% with 100 ns-1 μs pulses
+    /// let (converged, trajectory) = arc2.program_to_target(3, 19, 10_000.0, 0.05,
+    ///     ProgramPolicy::FixedStep(0.05), 0.2, (0.8, 2.0), (-0.8, -2.5),
+    ///     (100, 1_000), 200)?;
+    ///
+    /// println!("Converged: {}; final resistance: {}", converged,
+    ///     trajectory.last().unwrap().2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn program_to_target(&mut self, low: usize, high: usize, target: f32,
+        tolerance: f32, policy: ProgramPolicy, vread: f32, vset: (f32, f32),
+        vreset: (f32, f32), pw_nanos: (u128, u128), budget: usize)
+        -> Result<(bool, Vec<(f32, u128, f32)>), ArC2Error> {
+
+        if target.is_nan() || target <= 0.0 {
+            return Err(ArC2Error::InvalidValue(target));
+        }
+
+        for v in &[tolerance, vread, vset.0, vset.1, vreset.0, vreset.1] {
+            if v.is_nan() {
+                return Err(ArC2Error::InvalidValue(*v));
+            }
+        }
+
+        let mut trajectory: Vec<(f32, u128, f32)> = Vec::with_capacity(budget + 1);
+
+        let mut res = _resistance(vread, self.read_one(low, high, vread)?);
+        trajectory.push((0.0, 0, res));
+
+        let mut side = _tolerance_side(res, target, tolerance);
+
+        // Amplitude windows for the two polarities as (low bound, high bound)
+        // magnitudes; the low bound is only modified by bisection
+        let mut windows = [(vset.0.abs(), vset.1.abs()), (vreset.0.abs(), vreset.1.abs())];
+        let signs = [vset.0.signum(), vreset.0.signum()];
+        let mut amplitude = windows[0].0;
+        let mut width = pw_nanos.0;
+        let mut prev_side = side;
+
+        for _ in 0..budget {
+
+            if side == 0 {
+                return Ok((true, trajectory));
+            }
+
+            // polarity index; 0 for set, 1 for reset
+            let pidx = if side > 0 { 0 } else { 1 };
+
+            // polarity switched or first pulse; start over
+            if side != prev_side || trajectory.len() == 1 {
+                amplitude = match policy {
+                    ProgramPolicy::Bisection => (windows[pidx].0 + windows[pidx].1) / 2.0,
+                    _ => windows[pidx].0
+                };
+                width = pw_nanos.0;
+            }
+
+            let voltage = signs[pidx] * amplitude;
+            let current = self.pulseread_one(low, high, voltage, width, vread)?;
+            res = _resistance(vread, current);
+            trajectory.push((voltage, width, res));
+
+            prev_side = side;
+            side = _tolerance_side(res, target, tolerance);
+
+            // Determine the next amplitude for the same polarity
+            let at_max = amplitude >= windows[pidx].1;
+            match policy {
+                ProgramPolicy::FixedStep(vstep) => {
+                    amplitude = (amplitude + vstep.abs()).min(windows[pidx].1);
+                },
+                ProgramPolicy::Adaptive(min, max) => {
+                    let decades = (res / target).log10().abs();
+                    let step = (decades * max.abs()).max(min.abs()).min(max.abs());
+                    amplitude = (amplitude + step).min(windows[pidx].1);
+                },
+                ProgramPolicy::Bisection => {
+                    if side == prev_side {
+                        // fell short
+                        windows[pidx].0 = amplitude;
+                    } else if side != 0 {
+                        // overshoot
+                        windows[pidx].1 = amplitude;
+                    }
+                    amplitude = (windows[pidx].0 + windows[pidx].1) / 2.0;
+                },
+            };
+
+            // Amplitude is maxed out; try longer pulses
+            if at_max && side == prev_side {
+                width = (width * 2).min(pw_nanos.1);
+            }
+        }
+
+        Ok((side == 0, trajectory))
+    }
+
+    /// Program multiple crosspoints to target resistances in batches
+    ///
+    /// This is a batched variant of [`Instrument::program_to_target`] that avoids a
+    /// round-trip to the host for every pulse. Argument `targets` contains tuples of
+    /// `(low, high, target resistance)`. Crosspoints are grouped by their `low` channel
+    /// and for every batch `k` pulse+read pairs are speculatively queued for all
+    /// crosspoints that are not yet within `tolerance` followed by a single execution.
+    /// Within a batch the amplitude of the pulses of each crosspoint is increased by
+    /// `vstep` after every pulse, starting from the first value of the `vset` or `vreset`
+    /// window (depending on whether the resistance is above or below the target) and up
+    /// to its second value. Crosspoints sharing a `low` channel are pulsed in parallel
+    /// with the high speed drivers (see [`Instrument::pulse_slice_fast_open`]) with the
+    /// `low` channel held at 0.0 V and all other lines of the same type left floating
+    /// to limit disturbance of unselected crosspoints. Pulses of opposite polarity are
+    /// applied in separate rounds. Pulse width is fixed to `pw_nanos` which must be
+    /// shorter than 500 ms. After each batch the readouts are analysed: if the
+    /// target was still not reached the next batch continues from where the amplitude
+    /// ladder of the previous one stopped; if the target was overshot polarity is
+    /// switched and the amplitude starts over. As pulses are queued speculatively a
+    /// crosspoint might enter the tolerance window mid-batch and leave it with a
+    /// subsequent pulse of the same batch; only the final read of each batch is used to
+    /// decide convergence so smaller values of `k` trade speed for accuracy. The
+    /// process stops when all crosspoints are in tolerance or after `max_batches`
+    /// batches.
+    ///
+    /// The function returns, in the order of `targets`, whether each crosspoint reached
+    /// its target along with its trajectory in the same format as
+    /// [`Instrument::program_to_target`].
+    ///
+    /// ```no_run
+    /// use libarc2::{Instrument};
+    /// # use libarc2::ArC2Error;
+    ///
+    /// # fn main() -> Result<(), ArC2Error> {
+    /// let mut arc2 = Instrument::open_with_fw(0, "fw.bin", true, true).unwrap();
+    ///
+    /// // Program the first 4 crosspoints of row 3 to different resistances,
+    /// // with 8 pulse+read pairs per batch
+    /// let targets = vec![(3, 19, 5_000.0), (3, 20, 10_000.0), (3, 21, 20_000.0),
+    ///     (3, 22, 40_000.0)];
+    /// let results = arc2.program_to_target_batched(&targets, 0.05, 0.05, 0.2,
+    ///     (0.8, 2.0), (-0.8, -2.5), 1_000, 8, 50)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn program_to_target_batched(&mut self, targets: &[(usize, usize, f32)],
+        tolerance: f32, vstep: f32, vread: f32, vset: (f32, f32), vreset: (f32, f32),
+        pw_nanos: u128, k: usize, max_batches: usize)
+        -> Result<Vec<(bool, Vec<(f32, u128, f32)>)>, ArC2Error> {
+
+        for (_, _, target) in targets {
+            if target.is_nan() || *target <= 0.0 {
+                return Err(ArC2Error::InvalidValue(*target));
+            }
+        }
+
+        for v in &[tolerance, vstep, vread, vset.0, vset.1, vreset.0, vreset.1] {
+            if v.is_nan() {
+                return Err(ArC2Error::InvalidValue(*v));
+            }
+        }
+
+        if k == 0 {
+            return Err(ArC2Error::InvalidValue(0.0));
+        }
+
+        // polarity sign, start and max amplitude depending on the side of
+        // the target the crosspoint currently is
+        let polarity = |side: i8| -> (f32, f32, f32) {
+            if side > 0 {
+                (vset.0.signum(), vset.0.abs(), vset.1.abs())
+            } else {
+                (vreset.0.signum(), vreset.0.abs(), vreset.1.abs())
+            }
+        };
+
+        // Group crosspoints by low channel; these will be pulsed and read
+        // in parallel. Groups hold indices into `targets`.
+        let rows = _group_by_low(targets.iter().map(|t| t.0));
+
+        let mut trajectories: Vec<Vec<(f32, u128, f32)>> = vec![Vec::new(); targets.len()];
+        let mut sides: Vec<i8> = vec![0; targets.len()];
+        let mut amplitudes: Vec<f32> = vec![0.0; targets.len()];
+
+        // Initial read of all crosspoints
+        self.reset_dacs()?;
+        let mut chunks: Vec<Chunk> = Vec::with_capacity(rows.len());
+        for (low, idxs) in &rows {
+            let highs: Vec<usize> = idxs.iter().map(|i| targets[*i].1).collect();
+            chunks.push(self._read_slice_inner(*low, &highs, vidx!(-vread))?);
+        }
+        self.ground_all_fast()?.execute()?;
+        self.wait();
+
+        for ((_, idxs), mut chunk) in rows.iter().zip(chunks) {
+            let data = self.read_chunk(&mut chunk, &DataMode::All, &ReadType::Current)?;
+            for i in idxs {
+                let (_, high, target) = targets[*i];
+                let res = _resistance(vread, data[high]);
+                trajectories[*i].push((0.0, 0, res));
+                sides[*i] = _tolerance_side(res, target, tolerance);
+                amplitudes[*i] = polarity(sides[*i]).1;
+            }
+        }
+
+        let cl_nanos = [Some(pw_nanos); 8];
+
+        for _ in 0..max_batches {
+
+            if sides.iter().all(|s| *s == 0) {
+                break;
+            }
+
+            self.reset_dacs()?;
+
+            // (crosspoints pulsed, chunks of the batch) per row
+            let mut batch: Vec<(Vec<usize>, Vec<Chunk>)> = Vec::new();
+
+            for (low, idxs) in &rows {
+
+                let active: Vec<usize> = idxs.iter()
+                    .filter(|i| sides[**i] != 0)
+                    .copied()
+                    .collect();
+
+                if active.is_empty() {
+                    continue;
+                }
+
+                let highs: Vec<usize> = idxs.iter().map(|i| targets[*i].1).collect();
+                let (same, _) = _array_unselected(*low, &[]);
+                let mut row_chunks: Vec<Chunk> = Vec::with_capacity(k);
+
+                for step in 0..k {
+
+                    // pulses of each polarity are done in separate rounds
+                    // as HS polarity bits are set per cluster
+                    let mut set: Vec<(usize, f32, f32)> = Vec::new();
+                    let mut reset: Vec<(usize, f32, f32)> = Vec::new();
+
+                    for i in &active {
+                        let (sign, _, max) = polarity(sides[*i]);
+                        let v = sign * (amplitudes[*i] + vstep.abs()*(step as f32)).min(max);
+                        if sides[*i] > 0 {
+                            set.push((targets[*i].1, v, 0.0));
+                        } else {
+                            reset.push((targets[*i].1, v, 0.0));
+                        }
+                    }
+
+                    self.open_channels(&same)?;
+                    // wait for every group to finish before reconfiguring
+                    // the drivers for the next one or grounding
+                    for group in &[set, reset] {
+                        if !group.is_empty() {
+                            self.pulse_slice_fast_open(group, &cl_nanos, true)?
+                                .add_delay(pw_nanos)?;
+                        }
+                    }
+                    self.ground_all_fast()?;
+                    row_chunks.push(self._read_slice_inner(*low, &highs, vidx!(-vread))?);
+                }
+
+                batch.push((active, row_chunks));
+            }
+
+            self.ground_all_fast()?.execute()?;
+            self.wait();
+
+            for (active, row_chunks) in batch {
+
+                for (step, mut chunk) in row_chunks.into_iter().enumerate() {
+                    let data = self.read_chunk(&mut chunk, &DataMode::All, &ReadType::Current)?;
+                    for i in &active {
+                        let (sign, _, max) = polarity(sides[*i]);
+                        let v = sign * (amplitudes[*i] + vstep.abs()*(step as f32)).min(max);
+                        let res = _resistance(vread, data[targets[*i].1]);
+                        trajectories[*i].push((v, pw_nanos, res));
+                    }
+                }
+
+                // Decide the next batch based on the last read
+                for i in &active {
+                    let res = trajectories[*i].last().unwrap().2;
+                    let side = _tolerance_side(res, targets[*i].2, tolerance);
+                    if side == sides[*i] {
+                        let (_, _, max) = polarity(side);
+                        amplitudes[*i] = (amplitudes[*i] + vstep.abs()*(k as f32)).min(max);
+                    } else if side != 0 {
+                        amplitudes[*i] = polarity(side).1;
+                    }
+                    sides[*i] = side;
+                }
+            }
+        }
+
+        Ok(sides.iter().zip(trajectories).map(|(s, t)| (*s == 0, t)).collect())
+    }
+
+    /// Program a conductance matrix onto a crossbar
+    ///
+    /// This will program the conductances in `weights` (in S, one row per channel in `rows`
+    /// and one column per channel in `cols`) onto the crosspoints formed between `rows`
+    /// (acting as the low channels) and `cols`. The whole matrix is first read and only
+    /// cells that are not within `tolerance` (relative) of their target are programmed, so
+    /// the function can be safely called again to resume or touch up a previously
+    /// programmed matrix. Cells are then programmed with
+    /// [`Instrument::program_to_target_batched`] (see there for `vstep`, `vread`, `vset`,
+    /// `vreset`, `pw_nanos`, `k` and `max_batches`) in two stages: cells that are more
+    /// than a factor of 2 away from their target are programmed first and the rest are
+    /// programmed afterwards, so that the large, more disruptive, programming steps happen
+    /// first and fine tuning last. Programming a cell can disturb cells already programmed;
+    /// to account for that the read-all and programming steps are repeated up to `passes`
+    /// times or until all cells are within tolerance.
+    ///
+    /// The function returns the achieved conductance matrix along with the relative error
+    /// of each cell, `(G - G_target)/G_target`, both with the same layout as `weights`.
+    ///
+    /// ```no_run
+    /// use libarc2::{Instrument};
+    /// # use libarc2::ArC2Error;
+    ///
+    /// # fn main() -> Result<(), ArC2Error> {
+    /// let mut arc2 = Instrument::open_with_fw(0, "fw.bin", true, true).unwrap();
+    ///
+    /// // 2×2 matrix on rows 0, 1 and columns 16, 17
+    /// let weights = vec![vec![1e-4, 5e-5], vec![2e-5, 1e-5]];
+    /// let (achieved, errors) = arc2.program_matrix(&weights, &[0, 1], &[16, 17], 0.05,
+    ///     0.05, 0.2, (0.8, 2.0), (-0.8, -2.5), 1_000, 8, 50, 3)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn program_matrix(&mut self, weights: &[Vec<f32>], rows: &[usize], cols: &[usize],
+        tolerance: f32, vstep: f32, vread: f32, vset: (f32, f32), vreset: (f32, f32),
+        pw_nanos: u128, k: usize, max_batches: usize, passes: usize)
+        -> Result<(Vec<Vec<f32>>, Vec<Vec<f32>>), ArC2Error> {
+
+        if weights.len() != rows.len() {
+            return Err(ArC2Error::DimensionError(weights.len(), rows.len()));
+        }
+
+        for row in weights {
+            if row.len() != cols.len() {
+                return Err(ArC2Error::DimensionError(row.len(), cols.len()));
+            }
+            for g in row {
+                if g.is_nan() || *g <= 0.0 {
+                    return Err(ArC2Error::InvalidValue(*g));
+                }
+            }
+        }
+
+        let mut achieved = self._read_conductances(rows, cols, vread)?;
+
+        for _ in 0..passes {
+
+            // Cells out of tolerance as (low, high, target resistance), split
+            // into coarse and fine according to their distance from the target
+            let mut coarse: Vec<(usize, usize, f32)> = Vec::new();
+            let mut fine: Vec<(usize, usize, f32)> = Vec::new();
+            for (r, row) in weights.iter().enumerate() {
+                for (c, target) in row.iter().enumerate() {
+                    let res = 1.0 / achieved[r][c];
+                    if _tolerance_side(res, 1.0 / target, tolerance) == 0 {
+                        continue;
+                    }
+                    let cell = (rows[r], cols[c], 1.0 / target);
+                    // a NaN distance (no conductance) is treated as far
+                    if (achieved[r][c] / target).log10().abs() <= PROGCOARSE {
+                        fine.push(cell);
+                    } else {
+                        coarse.push(cell);
+                    }
+                }
+            }
+
+            if coarse.is_empty() && fine.is_empty() {
+                break;
+            }
+
+            // Program batches run all their cells in parallel, hence
+            // the separate calls
+            for targets in &[coarse, fine] {
+                if targets.is_empty() {
+                    continue;
+                }
+                self.program_to_target_batched(targets, tolerance, vstep, vread, vset,
+                    vreset, pw_nanos, k, max_batches)?;
+            }
+
+            achieved = self._read_conductances(rows, cols, vread)?;
+        }
 
-        let bias_channels = match order {
-            BiasOrder::Rows => &*ALL_WORDS,
-            BiasOrder::Columns => &*ALL_BITS
-        };
+        let errors: Vec<Vec<f32>> = achieved.iter()
+            .zip(weights)
+            .map(|(a, w)| a.iter().zip(w).map(|(g, t)| (g - t) / t).collect())
+            .collect();
 
-        for chan in bias_channels {
-            results.append(&mut self.read_slice(*chan, vread)?);
+        Ok((achieved, errors))
+    }
+
+    /// Read the conductances of all crosspoints between `rows` and `cols`
+    /// returning a matrix with one row per channel in `rows`.
+    fn _read_conductances(&mut self, rows: &[usize], cols: &[usize], vread: f32)
+        -> Result<Vec<Vec<f32>>, ArC2Error> {
+
+        self.reset_dacs()?;
+
+        let mut chunks: Vec<Chunk> = Vec::with_capacity(rows.len());
+        for low in rows {
+            chunks.push(self._read_slice_inner(*low, cols, vidx!(-vread))?);
         }
+        self.ground_all_fast()?.execute()?;
+        self.wait();
 
-        Ok(results)
+        let mut result: Vec<Vec<f32>> = Vec::with_capacity(rows.len());
+        for mut chunk in chunks {
+            let data = self.read_chunk(&mut chunk, &DataMode::All, &ReadType::Current)?;
+            result.push(cols.iter().map(|c| 1.0 / _resistance(vread, data[*c])).collect());
+        }
 
+        Ok(result)
     }
 
-    fn _vread_channels_deferred_chunk(&mut self, chans: &[usize], avg: bool) -> Result<Chunk, ArC2Error> {
+    /// Apply weight updates for hardware-in-the-loop training
+    ///
+    /// This takes a sparse list of weight updates, as `(low, high, Δw)` tuples, and applies
+    /// them as high speed pulses on the corresponding crosspoints. Every update is converted
+    /// into `round(|Δw|/dw_per_pulse)` identical pulses of `pw_nanos` width at `vpot`, for
+    /// positive updates, or `vdep`, for negative ones. Updates are grouped by their `low`
+    /// channel, which is held at 0.0 V while all other lines of the same type are left
+    /// floating, and the pulses on the `high` channels are packed into as few parallel
+    /// pulsing rounds as the cluster timing and polarity constraints of the high speed
+    /// drivers allow (see [`Instrument::pulse_slice_fast_open`]); every round is held for
+    /// its longest pulse and then the lines are grounded. After its updates the
+    /// crosspoints of every group are read at `vread`. Everything is executed at once and
+    /// the post-update conductances are returned in the order of `updates`.
+    ///
+    /// ```no_run
+    /// use libarc2::{Instrument};
+    /// # use libarc2::ArC2Error;
+    ///
+    /// # fn main() -> Result<(), ArC2Error> {
+    /// let mut arc2 = Instrument::open_with_fw(0, "fw.bin", true, true).unwrap();
+    ///
+    /// let updates = vec![(0, 16, 3e-6), (0, 17, -1e-6), (1, 16, 2e-6)];
+    /// let conductances = arc2.apply_updates(&updates, 1e-6, 1.2, -1.5, 1_000, 0.2)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn apply_updates(&mut self, updates: &[(usize, usize, f32)], dw_per_pulse: f32,
+        vpot: f32, vdep: f32, pw_nanos: u128, vread: f32) -> Result<Vec<f32>, ArC2Error> {
 
-        // Create a new mask and populate it with the specified channels
-        let mut mask = ChanMask::none();
+        for v in &[dw_per_pulse, vpot, vdep, vread] {
+            if v.is_nan() {
+                return Err(ArC2Error::InvalidValue(*v));
+            }
+        }
 
-        for c in chans {
-            mask.set_enabled(*c, true);
+        if dw_per_pulse == 0.0 {
+            return Err(ArC2Error::InvalidValue(dw_per_pulse));
         }
 
-        let chunk = self.make_chunk()?;
+        for (_, _, dw) in updates {
+            if dw.is_nan() {
+                return Err(ArC2Error::InvalidValue(*dw));
+            }
+        }
 
-        let mut voltageread = VoltageRead::new(&mask, avg, chunk.addr(),
-            chunk.flag_addr(), VALUEAVAILFLAG);
-        self.process(voltageread.compile())?;
+        // Group updates by low channel; groups hold indices into `updates`
+        let rows = _group_by_low(updates.iter().map(|u| u.0));
 
-        Ok(chunk)
+        self.reset_dacs()?;
 
-    }
+        let mut chunks: Vec<Chunk> = Vec::with_capacity(rows.len());
 
-    /// Do a voltage read on all the specified channels.
-    /// This function will read the voltage on all specified channels and return their
-    /// values in ascending channel order. Set `avg` to `true` to enable averaging
-    /// but that will increase readout time from 10 to 320 μs. Calling this function
-    /// will immediately flush the FIFO and return the value.
-    pub fn vread_channels(&mut self, uchans: &[usize], avg: bool) -> Result<Vec<f32>, ArC2Error> {
+        for (low, idxs) in &rows {
 
-        let mut chans = uchans.to_vec();
-        chans.sort();
+            let mut pulses: Vec<(usize, f32, f32, u128)> = Vec::new();
+            for i in idxs {
+                let (_, high, dw) = updates[*i];
+                let v = if dw >= 0.0 { vpot } else { vdep };
+                let n = (dw.abs() / dw_per_pulse.abs()).round() as usize;
+                for _ in 0..n {
+                    pulses.push((high, v, 0.0, pw_nanos));
+                }
+            }
 
-        let mut chunk = self._vread_channels_deferred_chunk(&chans, avg)?;
-        self.execute()?;
+            let (same, _) = _array_unselected(*low, &[]);
+
+            // The low channel must be explicitly held at 0.0 V; after a
+            // float or a fresh open it is not driven at all
+            self.config_channels(&[(*low as u16, 0.0)], None)?;
+
+            for round in hs_pulse_rounds(&pulses)? {
+                self.open_channels(&same)?;
+                // this waits for the pulses of the round to finish so the
+                // ground that follows does not truncate them
+                self._pulse_hs_round(&pulses, &round)?;
+                self.ground_all_fast()?;
+            }
+
+            let highs: Vec<usize> = idxs.iter().map(|i| updates[*i].1).collect();
+            chunks.push(self._read_slice_inner(*low, &highs, vidx!(-vread))?);
+        }
+
+        self.ground_all_fast()?.execute()?;
         self.wait();
-        let mut results: Vec<f32> = Vec::with_capacity(chans.len());
 
-        let res = self.read_chunk(&mut chunk, &DataMode::All, &ReadType::Voltage)?;
+        let mut result = vec![f32::NAN; updates.len()];
+        for ((_, idxs), mut chunk) in rows.iter().zip(chunks) {
+            let data = self.read_chunk(&mut chunk, &DataMode::All, &ReadType::Current)?;
+            for i in idxs {
+                result[*i] = 1.0 / _resistance(vread, data[updates[*i].1]);
+            }
+        }
 
-        for c in &chans {
-            results.push(res[*c]);
+        Ok(result)
+    }
+
+    /// Apply an arbitrary set of high speed pulses in parallel rounds
+    ///
+    /// This takes a list of pulses, as `(channel, active_v, normal_v, width)` tuples, packs
+    /// them into the minimum number of rounds compatible with the cluster timing and
+    /// polarity constraints of the high speed drivers using [`hs_pulse_rounds`], and queues
+    /// all rounds, one after the other, with [`Instrument::pulse_slice_fast_open`]. Every
+    /// round is held for the width of its longest pulse before the next one is configured.
+    /// Pulses on the same channel are not guaranteed to be applied in the order they are
+    /// given unless they are identical. As with other pulsing functions this needs to be
+    /// followed by an [`Instrument::execute`]. The function returns the partition, as
+    /// indices into `pulses` for every round.
+    ///
+    /// ```no_run
+    /// use libarc2::{Instrument};
+    /// # use libarc2::ArC2Error;
+    ///
+    /// # fn main() -> Result<(), ArC2Error> {
+    /// let mut arc2 = Instrument::open_with_fw(0, "fw.bin", true, true).unwrap();
+    ///
+    /// let pulses = vec![(16, 1.5, 0.0, 1_000), (17, -1.5, 0.0, 1_000),
+    ///     (24, 1.5, 0.0, 5_000), (16, 1.5, 0.0, 1_000)];
+    /// let rounds = arc2.pulse_scheduled(&pulses)?;
+    /// arc2.execute()?;
+    ///
+    /// println!("{} rounds: {:?}", rounds.len(), rounds);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn pulse_scheduled(&mut self, pulses: &[(usize, f32, f32, u128)])
+        -> Result<Vec<Vec<usize>>, ArC2Error> {
+
+        for (_, active_v, normal_v, _) in pulses {
+            for v in &[active_v, normal_v] {
+                if v.is_nan() {
+                    return Err(ArC2Error::InvalidValue(**v));
+                }
+            }
         }
 
-        Ok(results)
+        let rounds = hs_pulse_rounds(pulses)?;
+
+        for round in &rounds {
+            self._pulse_hs_round(pulses, round)?;
+        }
 
+        Ok(rounds)
     }
 
-    /// Do a voltage read on the specified channels. Unlike [`Instrument::vread_channels`]
-    /// execution of this command is deferred, which means that it needs to be followed
-    /// by an [`Instrument::execute`] call (at some point). As the equivalent current
-    /// function this is meant to be used in a chain of commands without immediately
-    /// flushing the FIFO. If you want to bypass the command queue and get an immediate
-    /// voltage read use [`Instrument::vread_channels`] instead.
-    pub fn vread_channels_deferred(&mut self, uchans: &[usize], avg: bool) -> Result<&mut Self, ArC2Error> {
+    /// Apply one round of pulses as produced by [`hs_pulse_rounds`] using the
+    /// high speed drivers.
+    fn _pulse_hs_round(&mut self, pulses: &[(usize, f32, f32, u128)], round: &[usize])
+        -> Result<&mut Self, ArC2Error> {
 
-        let mut chans = uchans.to_vec();
-        chans.sort();
+        let mut cl_nanos: [Option<u128>; 8] = [None; 8];
+        let mut chans: Vec<(usize, f32, f32)> = Vec::with_capacity(round.len());
 
-        let chunk = self._vread_channels_deferred_chunk(&chans, avg)?;
-        self._sender.send(Some(chunk))?;
-        Ok(self)
+        for idx in round {
+            let (chan, active_v, normal_v, width) = pulses[*idx];
+            cl_nanos[chan/8] = Some(width);
+            chans.push((chan, active_v, normal_v));
+        }
 
+        self.pulse_slice_fast_open(&chans, &cl_nanos, true)?;
+
+        // Hold until the longest pulse of the round is over; anything
+        // queued before that would cut the pulses short
+        let longest = cl_nanos.iter().filter_map(|n| *n).max().unwrap_or(0u128);
+        self.add_delay(longest)
     }
 
-    /// Connect selected channels to the current source targeting a specific
-    /// current. Setting `preset_range` to `true` will generate extra instructions
-    /// to reconfigure the source to the necessary range for the selected
-    /// current. Obviously connecting more than one channels to the current
-    /// source will split (probably unevenly) the current between the connected
-    /// DUTs. Please note that configuring the source only affects the dynamic end
-    /// of the connection (where current is sourced/sinked from/to). The static
-    /// end of any DUT must be configured separately.
-    pub fn config_current_source(&mut self, chans: &[usize], current: f32,
-        float: bool, preset_range: bool) -> Result<&mut Self, ArC2Error> {
+    /// Electroform a crosspoint with emulated current compliance
+    ///
+    /// This will apply a series of pulses of `pw_nanos` width on the crosspoint defined by
+    /// `low` and `high`, starting from `vstart` and increasing by `vstep` up to and including
+    /// `vmax`, following the conventions of [`Instrument::pulse_one`]. Every pulse is
+    /// followed by a current read at the pulse voltage and the ramp is aborted as soon as the
+    /// absolute current exceeds `icc`. As there is no hardware compliance in these paths the
+    /// instrument is only synchronised every `batch` steps: a smaller batch will respond
+    /// faster to the forming event (with `batch` = 1 no further pulses are applied after the
+    /// compliance is exceeded) at the expense of speed. If `cc_nanos` is not `None` the
+    /// process is finished with a current of `icc` (of the polarity of the ramp) forced
+    /// through `high` for the specified duration using the current source, with `low` held
+    /// at 0.0 V, to stabilise the device under a limited current.
+    ///
+    /// The function returns the voltage at which compliance was reached, or `None` if the
+    /// device did not form, and the resistance of the device read at `vread` at the end of
+    /// the process. The crosspoint is grounded afterwards.
+    ///
+    /// ```no_run
+    /// use libarc2::{Instrument};
+    /// # use libarc2::ArC2Error;
+    ///
+    /// # fn main() -> Result<(), ArC2Error> {
+    /// let mut arc2 = Instrument::open_with_fw(0, "fw.bin", true, true).unwrap();
+    ///
+    /// // Form crosspoint 3 × 19 with 100 μs pulses from 1 to 5 V in 50 mV steps,
+    /// // compliance at 100 μA, then stabilise at 100 μA for 1 ms
+    /// let (vform, res) = arc2.form(3, 19, 1.0, 0.05, 5.0, 100_000, 100e-6, 0.2, 1,
+    ///     Some(1_000_000))?;
+    ///
+    /// match vform {
+    ///     Some(v) => println!("Formed at {} V; resistance {} Ω", v, res),
+    ///     None => println!("Device did not form; resistance {} Ω", res)
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn form(&mut self, low: usize, high: usize, vstart: f32, vstep: f32, vmax: f32,
+        pw_nanos: u128, icc: f32, vread: f32, batch: usize, cc_nanos: Option<u128>)
+        -> Result<(Option<f32>, f32), ArC2Error> {
 
-        if current.is_nan() {
-            return Err(ArC2Error::InvalidValue(current))
+        for v in &[vstart, vstep, vmax, vread] {
+            if v.is_nan() {
+                return Err(ArC2Error::InvalidValue(*v));
+            }
         }
 
-        let cvolt_base = current.signum()*10.0;
-        if preset_range {
-            let mut chanconf = ChannelConf::new();
-            if float {
-                // ensure that channels connected to the current source
-                // are floating
-                for c in chans {
-                    chanconf.set(*c, ChannelState::Open);
+        if icc.is_nan() || icc == 0.0 {
+            return Err(ArC2Error::InvalidValue(icc));
+        }
+
+        // The step must lead from vstart towards vmax, otherwise the ramp
+        // would collapse to a single pulse at vmax
+        if vstep == 0.0 || (vmax - vstart)*vstep < 0.0 {
+            return Err(ArC2Error::RampOperationError(vstart, vmax, vstep));
+        }
+
+        let mut voltages = RampShape::Linear.voltages(vstart, vstep, vmax, 1)?;
+        if voltages.last().map_or(true, |v| *v != vmax) {
+            voltages.push(vmax);
+        }
+
+        let mut vform: Option<f32> = None;
+
+        for steps in voltages.chunks(batch.max(1)) {
+
+            self.reset_dacs()?;
+            let mut chunks: Vec<Chunk> = Vec::with_capacity(steps.len());
+
+            for v in steps {
+                if pw_nanos < 500_000_000u128 {
+                    self.pulse_one_fast(low, high, *v, pw_nanos)?;
+                } else {
+                    self.pulse_one_slow(low, high, *v, pw_nanos)?;
+                }
+                self.ground_all_fast()?;
+                chunks.push(self._read_slice_inner(low, &[high], vidx!(-v))?);
+                self.ground_all_fast()?;
+            }
+
+            self.execute()?;
+            self.wait();
+
+            // all chunks must be read to be freed, even after
+            // compliance has been exceeded
+            for (v, mut chunk) in steps.iter().zip(chunks) {
+                let current = self.read_chunk(&mut chunk, &DataMode::All,
+                    &ReadType::Current)?[high];
+                if vform.is_none() && current.abs() > icc.abs() {
+                    vform = Some(*v);
                 }
             }
-            self.config_aux_channels(
-                &[(AuxDACFn::CSET, cvolt_base), (AuxDACFn::CREF, cvolt_base)])?;
+
+            if vform.is_some() {
+                break;
+            }
         }
-        Ok(self)
+
+        if let (Some(_), Some(nanos)) = (vform, cc_nanos) {
+            let current = icc.abs().copysign(vstep);
+            self.config_channels(&[(low as u16, 0.0)], None)?;
+            self.force_current(&[high], current, nanos, false)?;
+            self.connect_to_current_source(&[])?
+                .ground_all()?
+                .execute()?;
+            self.wait();
+        }
+
+        let res = _resistance(vread, self.read_one(low, high, vread)?);
+
+        Ok((vform, res))
     }
 
     /// Perform a series of current reads on the specified channels, with an optional delay.
@@ -2075,66 +4734,246 @@ impl Instrument {
     #[cfg(feature="flag_addresses")]
     fn wait_for_flag(&mut self, chunk: &Chunk) -> Result<(), ArC2Error> {
 
-        let mut counter: u64 = 0;
-        let mut exponent: u32 = 0;
+        let mut counter: u64 = 0;
+        let mut exponent: u32 = 0;
+
+        loop {
+            if self.value_available(&chunk)? {
+                return Ok(())
+            }
+
+            // limit maximum polling to 100 ms
+            if exponent < 5 {
+                if counter == 9 {
+                    counter = 0;
+                    exponent += 1;
+                } else {
+                    counter += 1;
+                }
+            }
+
+            let wait = std::time::Duration::from_nanos(10u64.pow(exponent) * 1000u64);
+            std::thread::sleep(wait);
+        }
+
+    }
+
+    // Variant of wait for internal use when a lock to a bl::Device has already
+    // been acquired
+    fn __wait_no_lock(&self, efm: &bl::Device) {
+
+        let mut counter: u64 = 0;
+        let mut exponent: u32 = 0;
+
+        while self.__busy_no_lock(efm) {
+
+            // limit maximum polling to 100 ms
+            if exponent < 5 {
+                if counter == 9 {
+                    counter = 0;
+                    exponent += 1;
+                } else {
+                    counter += 1;
+                }
+            }
+
+            let wait = std::time::Duration::from_nanos(10u64.pow(exponent) * 1000u64);
+            std::thread::sleep(wait);
+        }
+    }
+
+    /// Apply a pulse between the specified channels. A voltage of `-voltage/2` will be
+    /// applied to the `low` channel `voltage/2` to the `high` channel. When `preset_state`
+    /// is true the state of high speed drivers will be initialised before the actual pulsing
+    /// sequence begins.
+    pub fn pulse_one(&mut self, low: usize, high: usize, voltage: f32, nanos: u128)
+        -> Result<&mut Self, ArC2Error> {
+
+        // use the high speed driver for all pulses faster than 500 ms
+        if nanos < 500_000_000u128 {
+            self.pulse_one_fast(low, high, voltage, nanos)?;
+        } else {
+            self.pulse_one_slow(low, high, voltage, nanos)?;
+        }
+
+        Ok(self)
+    }
+
+    /// Perform a current read on a transistor-selected (1T1R) crosspoint
+    ///
+    /// The crosspoint is addressed by its `word` channel, which is treated as the low
+    /// channel, its `bit` channel, which is treated as the high channel, and the selector
+    /// channel driving the transistor `gate`. The selector pull-up level (SELH) is set to
+    /// `vgate` and the pull-down level (SELL) to 0.0 V, so that only the addressed gate is
+    /// turned on, then the read is performed as with [`Instrument::read_one`] and finally
+    /// all selectors are pulled down again. Settling delays are inserted after every
+    /// change of the gate configuration. Since the current is limited by the transistor
+    /// `vgate` also sets the compliance of the operation.
+    ///
+    /// ```no_run
+    /// use libarc2::{Instrument};
+    /// # use libarc2::ArC2Error;
+    ///
+    /// # fn main() -> Result<(), ArC2Error> {
+    /// let mut arc2 = Instrument::open_with_fw(0, "fw.bin", true, true).unwrap();
+    ///
+    /// // Read word 3, bit 19 with gate on selector 2 at 1.8 V
+    /// let current = arc2.read_1t1r(3, 19, 2, 1.8, 0.2)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn read_1t1r(&mut self, word: usize, bit: usize, gate: usize, vgate: f32,
+        vread: f32) -> Result<f32, ArC2Error> {
+
+        for v in &[vgate, vread] {
+            if v.is_nan() {
+                return Err(ArC2Error::InvalidValue(*v));
+            }
+        }
+
+        self.reset_dacs()?;
+        self._select_gate(gate, vgate)?;
+
+        let mut chunk = self._read_slice_inner(word, &[bit], vidx!(-vread))?;
+
+        self.ground_all_fast()?;
+        self._release_gates()?
+            .execute()?;
+        self.wait();
+
+        let res = self.read_chunk(&mut chunk, &DataMode::All, &ReadType::Current)?;
+
+        Ok(res[bit])
+    }
+
+    /// Apply a pulse on a transistor-selected (1T1R) crosspoint
+    ///
+    /// This follows the same conventions as [`Instrument::read_1t1r`] for addressing and
+    /// gate control and as [`Instrument::pulse_one`] for the pulse itself: the gate is
+    /// turned on at `vgate`, the pulse of `voltage` and `nanos` duration is applied
+    /// between `word` (low) and `bit` (high) and then the crosspoint is grounded and all
+    /// gates are turned off. The transistor limits the current during the pulse
+    /// according to `vgate`. Similar to [`Instrument::pulse_one`] this needs to be
+    /// followed by an [`Instrument::execute`].
+    pub fn pulse_1t1r(&mut self, word: usize, bit: usize, gate: usize, vgate: f32,
+        voltage: f32, nanos: u128) -> Result<&mut Self, ArC2Error> {
+
+        for v in &[vgate, voltage] {
+            if v.is_nan() {
+                return Err(ArC2Error::InvalidValue(*v));
+            }
+        }
+
+        self._select_gate(gate, vgate)?;
+        self.pulse_one(word, bit, voltage, nanos)?;
+        self.ground_all_fast()?;
+        self._release_gates()
+    }
 
-        loop {
-            if self.value_available(&chunk)? {
-                return Ok(())
-            }
+    /// Turn on a single gate line through the selector circuits
+    fn _select_gate(&mut self, gate: usize, vgate: f32) -> Result<&mut Self, ArC2Error> {
+        self.config_aux_channels(&[(AuxDACFn::SELH, vgate), (AuxDACFn::SELL, 0.0)])?;
+        self.config_selectors(&[gate])?;
+        self.add_delay(SELSETTLE)
+    }
 
-            // limit maximum polling to 100 ms
-            if exponent < 5 {
-                if counter == 9 {
-                    counter = 0;
-                    exponent += 1;
-                } else {
-                    counter += 1;
-                }
+    /// Pull down all gate lines through the selector circuits
+    fn _release_gates(&mut self) -> Result<&mut Self, ArC2Error> {
+        self.config_selectors(&[])?;
+        self.add_delay(SELSETTLE)
+    }
+
+    /// Sweep the gate voltage of a three-terminal device (transfer characteristic)
+    ///
+    /// This will hold `drain` at `vdrain` and `source` at 0.0 V while the gate voltage,
+    /// applied as described by `gate` (see [`GateSource`]), is stepped from `vstart` to
+    /// `vstop` in `vstep` increments; `vstop` is always included. If `dual` is `true`
+    /// the gate is then swept back down to `vstart`. At every step the biases are held
+    /// for `dwell_nanos` before a current read is performed on `drain` and, if
+    /// `read_gate` is `true` and the gate is driven by a regular channel, on the gate
+    /// as well; gate currents cannot be read from the AUX supplies so `read_gate` has
+    /// no effect in that case. The source is always held at 0.0 V, which means the
+    /// drain bias also sets the drain-source voltage. One result is added to the
+    /// output buffer per step, use [`Instrument::pick_one`] to retrieve them. At the
+    /// end of the sweep all channels are grounded and, for AUX gates, the supply is
+    /// set back to 0.0 V. Similar to [`Instrument::generate_ramp`] this needs to be
+    /// followed by an [`Instrument::execute`].
+    ///
+    /// ```no_run
+    /// use libarc2::{Instrument, GateSource, DataMode, ReadType};
+    /// use libarc2::registers::AuxDACFn;
+    /// # use libarc2::ArC2Error;
+    ///
+    /// # fn main() -> Result<(), ArC2Error> {
+    /// let mut arc2 = Instrument::open_with_fw(0, "fw.bin", true, true).unwrap();
+    ///
+    /// // Drain on channel 19 at 100 mV, source on 3, gate on ARB1 swept
+    /// // -2.0 → 2.0 → -2.0 V in 50 mV steps with 1 ms dwell
+    /// arc2.generate_gate_sweep(GateSource::Aux(AuxDACFn::ARB1), 19, 3, 0.1,
+    ///     -2.0, 0.05, 2.0, true, 1_000_000, false)?
+    ///     .execute()?;
+    ///
+    /// while let Some(data) = arc2.pick_one(DataMode::All, ReadType::Current)? {
+    ///     let id = data[19];
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn generate_gate_sweep(&mut self, gate: GateSource, drain: usize, source: usize,
+        vdrain: f32, vstart: f32, vstep: f32, vstop: f32, dual: bool, dwell_nanos: u128,
+        read_gate: bool) -> Result<&mut Self, ArC2Error> {
+
+        if vdrain.is_nan() {
+            return Err(ArC2Error::InvalidValue(vdrain));
+        }
+
+        if let GateSource::Aux(func) = gate {
+            match func {
+                AuxDACFn::ARB1 | AuxDACFn::ARB2 | AuxDACFn::ARB3 | AuxDACFn::ARB4 => {},
+                _ => { return Err(ArC2Error::AuxFunctionError(func as usize)); }
             }
+        }
 
-            let wait = std::time::Duration::from_nanos(10u64.pow(exponent) * 1000u64);
-            std::thread::sleep(wait);
+        // A single triangle cycle is symmetric around `vstop`; the up
+        // leg is the first half of it, turning point included
+        let mut voltages = RampShape::Triangle.voltages(vstart, vstep, vstop, 1)?;
+        if !dual {
+            voltages.truncate((voltages.len() + 1)/2);
         }
 
-    }
+        let mut sense: Vec<usize> = vec![drain];
+        if let GateSource::Channel(chan) = gate {
+            if read_gate {
+                sense.push(chan);
+            }
+        }
 
-    // Variant of wait for internal use when a lock to a bl::Device has already
-    // been acquired
-    fn __wait_no_lock(&self, efm: &bl::Device) {
+        let sender = self._sender.clone();
 
-        let mut counter: u64 = 0;
-        let mut exponent: u32 = 0;
+        for vgate in voltages {
 
-        while self.__busy_no_lock(efm) {
+            let mut biased: Vec<(usize, f32)> = vec![(drain, vdrain), (source, 0.0)];
+            match gate {
+                GateSource::Channel(chan) => { biased.push((chan, vgate)); },
+                GateSource::Aux(func) => { self.config_aux_channels(&[(func, vgate)])?; }
+            };
 
-            // limit maximum polling to 100 ms
-            if exponent < 5 {
-                if counter == 9 {
-                    counter = 0;
-                    exponent += 1;
-                } else {
-                    counter += 1;
-                }
+            if dwell_nanos > 0u128 {
+                let chans: Vec<(u16, f32)> = biased.iter()
+                    .map(|(c, v)| (*c as u16, *v))
+                    .collect();
+                self.config_channels(&chans, None)?
+                    .add_delay(dwell_nanos)?;
             }
 
-            let wait = std::time::Duration::from_nanos(10u64.pow(exponent) * 1000u64);
-            std::thread::sleep(wait);
+            let raw = Instrument::_biased_to_raw(&biased)?;
+            let chunk = self._read_biased_inner(&raw, &sense, None, false)?;
+            sender.send(Some(chunk))?;
         }
-    }
-
-    /// Apply a pulse between the specified channels. A voltage of `-voltage/2` will be
-    /// applied to the `low` channel `voltage/2` to the `high` channel. When `preset_state`
-    /// is true the state of high speed drivers will be initialised before the actual pulsing
-    /// sequence begins.
-    pub fn pulse_one(&mut self, low: usize, high: usize, voltage: f32, nanos: u128)
-        -> Result<&mut Self, ArC2Error> {
 
-        // use the high speed driver for all pulses faster than 500 ms
-        if nanos < 500_000_000u128 {
-            self.pulse_one_fast(low, high, voltage, nanos)?;
-        } else {
-            self.pulse_one_slow(low, high, voltage, nanos)?;
+        self.ground_all_fast()?;
+        if let GateSource::Aux(func) = gate {
+            self.config_aux_channels(&[(func, 0.0)])?;
         }
 
         Ok(self)
@@ -2181,15 +5020,113 @@ impl Instrument {
 
     }
 
+    /// Apply a pulse between the specified channels while biasing the unselected lines
+    /// according to the specified [`BiasScheme`].
+    ///
+    /// Semantics of `low`, `high`, `voltage` and `nanos` are the same as in
+    /// [`Instrument::pulse_one`]. Channels are assumed to be arranged in a 32×32 array.
+    /// Unselected lines are the lines of the same type as `low` and unselected opposite
+    /// lines are the lines of the same type as `high`. For instance, a 2.0 V pulse with
+    /// [`BiasScheme::VThird`] will set `low` at -1.0 V, `high` at 1.0 V, the unselected
+    /// lines at 0.33 V and the unselected opposite lines at -0.33 V. Once the pulse is
+    /// over the DACs of all lines are set back to 0.0 V so that no unselected device is
+    /// left stressed; floating lines are left open.
+    pub fn pulse_one_with_scheme(&mut self, low: usize, high: usize, voltage: f32, nanos: u128,
+        scheme: BiasScheme) -> Result<&mut Self, ArC2Error> {
+
+        let (same, opposite) = _array_unselected(low, &[high]);
+        let unselected = self._prep_unselected(&same, &opposite,
+            scheme.levels(-voltage/2.0, voltage/2.0))?;
+
+        // use the high speed driver for all pulses faster than 500 ms
+        if nanos < 500_000_000u128 {
+            self._pulse_one_fast_biased(low, high, voltage, nanos, &unselected)?;
+        } else {
+            self._pulse_one_slow_biased(low, high, voltage, nanos, &unselected)?;
+        }
+
+        // release the array; pulses have been waited for above
+        self.ground_all_fast()
+    }
+
+    /// Apply a pulse to all channels with `chan` as the low potential channel while
+    /// biasing the unselected lines according to the specified [`BiasScheme`].
+    ///
+    /// Semantics of the arguments are the same as in [`Instrument::pulse_slice`]. As all
+    /// high channels of the slice are pulsed simultaneously, unselected lines are all the
+    /// lines of the same type as `chan`. As with [`Instrument::pulse_one_with_scheme`]
+    /// all lines are set back to 0.0 V once the pulse is over.
+    pub fn pulse_slice_with_scheme(&mut self, chan: usize, voltage: f32, nanos: u128,
+        scheme: BiasScheme) -> Result<&mut Self, ArC2Error> {
+
+        let highs = if (chan < 16) || ((32 <= chan) && (chan < 48)) {
+            &*ALL_WORDS
+        } else {
+            &*ALL_BITS
+        };
+
+        let (same, opposite) = _array_unselected(chan, highs);
+        let unselected = self._prep_unselected(&same, &opposite,
+            scheme.levels(-voltage/2.0, voltage/2.0))?;
+
+        // use the high speed driver for all pulses faster than 500 ms
+        if nanos < 500_000_000u128 {
+            self._pulse_slice_fast_biased(chan, voltage, nanos, None, &unselected)?;
+        } else {
+            self._pulse_slice_slow_biased(chan, voltage, nanos, None, &unselected)?;
+        }
+
+        // release the array; pulses have been waited for above
+        self.ground_all_fast()
+    }
+
+    /// Prepare the unselected lines of an array for biasing. If `levels` is `None`
+    /// the unselected lines will be opened, otherwise they will be AMP PRPed and switched
+    /// to VoltArb. This returns the list of `(channel, voltage)` that must be applied
+    /// to the DACs of the unselected lines; this list is empty for floating lines.
+    fn _prep_unselected(&mut self, same: &[usize], opposite: &[usize],
+        levels: Option<(f32, f32)>) -> Result<Vec<(usize, f32)>, ArC2Error> {
+
+        let mut chans = same.to_vec();
+        chans.extend_from_slice(opposite);
+
+        match levels {
+            Some((vsame, vopposite)) => {
+                self.amp_prep(Some(&chans))?;
+
+                let mut bias_conf = ChannelConf::new();
+                for c in &chans {
+                    bias_conf.set(*c, ChannelState::VoltArb);
+                }
+                let mut conf = UpdateChannel::from_regs_default_source(&bias_conf);
+                self.process(conf.compile())?;
+
+                let mut unselected: Vec<(usize, f32)> = Vec::with_capacity(chans.len());
+                unselected.extend(same.iter().map(|c| (*c, vsame)));
+                unselected.extend(opposite.iter().map(|c| (*c, vopposite)));
+
+                Ok(unselected)
+            },
+            None => {
+                self.open_channels(&chans)?;
+                Ok(vec![])
+            }
+        }
+    }
+
     /// Setup the biasing channels for multi-channel 2 terminal pulsing.  The `config` argument
     /// holds a list of bias pairs in the form of `(low ch, high ch, voltage)`.  This function will
     /// set the high channel to `voltage/2` and the low channel to `-voltage/2` if `differential`
     /// is `true` otherwise it will apply `-voltage` to the low channel and 0.0 to the high. If the
     /// `high_speed` argument is true then the DACs will be setup for high-speed pulsing as
     /// required by the High Speed drivers. No delays are introduced here as this will be handled
-    /// either by a standard Delay instruction or a High Speed timer.
-    pub(crate) fn setup_dacs_2t_pulsing(&mut self, config: &[(usize, usize, f32)], high_speed: bool, differential: bool)
-        -> Result<(), ArC2Error> {
+    /// either by a standard Delay instruction or a High Speed timer. Channels in `unselected`
+    /// will have their DACs set at the specified steady voltages. These are typically the
+    /// unselected lines of an array held at an intermediate voltage during pulsing (see
+    /// [`BiasScheme`]). The state of the unselected channels is not modified so they must
+    /// be configured separately beforehand.
+    pub(crate) fn setup_dacs_2t_pulsing(&mut self, config: &[(usize, usize, f32)], high_speed: bool,
+        differential: bool, unselected: &[(usize, f32)]) -> Result<(), ArC2Error> {
 
         // (idx, low, high); as required by SetDAC::from_channels().
         let mut channels: Vec<(u16, u16, u16)> = Vec::with_capacity(config.len()*2usize);
@@ -2248,6 +5185,20 @@ impl Instrument {
             self.process(i.compile())?;
         }
 
+        // Unselected channels; as with `ground_slice_fast` only the DAC
+        // settings are required, the UP CH instruction is ignored
+        if unselected.len() > 0 {
+            let input: Vec<(u16, u16, u16)> = unselected.iter()
+                .map(|(c, v)| (*c as u16, vidx!(*v), vidx!(*v)))
+                .collect();
+            let (_, setdacs) = SetDAC::from_channels(&input, None,
+                &ChannelState::Maintain, &ChannelState::Maintain)?;
+
+            for mut i in setdacs {
+                self.process(i.compile())?;
+            }
+        }
+
         self.process(&*UPDATE_DAC)
 
     }
@@ -2256,6 +5207,13 @@ impl Instrument {
     /// will *NOT* automatically flush output.
     fn pulse_one_slow(&mut self, low: usize, high: usize, voltage: f32, nanos: u128)
         -> Result<&mut Self, ArC2Error> {
+        self._pulse_one_slow_biased(low, high, voltage, nanos, &[])
+    }
+
+    /// Same as [`Instrument::pulse_one_slow`] while holding the `unselected` channels
+    /// at the specified voltages.
+    fn _pulse_one_slow_biased(&mut self, low: usize, high: usize, voltage: f32, nanos: u128,
+        unselected: &[(usize, f32)]) -> Result<&mut Self, ArC2Error> {
 
         // set high and low channels as HS drivers
         let mut bias_conf = ChannelConf::new();
@@ -2267,7 +5225,7 @@ impl Instrument {
         self.process(conf.compile())?;
 
         // setup a non-high speed differential pulsing scheme
-        self.setup_dacs_2t_pulsing(&[(low, high, voltage)], false, true)?;
+        self.setup_dacs_2t_pulsing(&[(low, high, voltage)], false, true, unselected)?;
         self.add_delay(nanos+30_000u128)?;
 
         Ok(self)
@@ -2278,6 +5236,13 @@ impl Instrument {
     /// automatically flush output.
     fn pulse_one_fast(&mut self, low: usize, high: usize, voltage: f32, nanos: u128)
         -> Result<&mut Self, ArC2Error> {
+        self._pulse_one_fast_biased(low, high, voltage, nanos, &[])
+    }
+
+    /// Same as [`Instrument::pulse_one_fast`] while holding the `unselected` channels
+    /// at the specified voltages.
+    fn _pulse_one_fast_biased(&mut self, low: usize, high: usize, voltage: f32, nanos: u128,
+        unselected: &[(usize, f32)]) -> Result<&mut Self, ArC2Error> {
 
         // set high and low channels as HS drivers
         let mut bias_conf = ChannelConf::new();
@@ -2323,7 +5288,7 @@ impl Instrument {
         // was -> self._tia_state = TIAState::Open(ChanMask::all());
         self._tia_state.set_channels_enabled(&[low, high], true);
         // setup a high-speed differential pulsing scheme
-        self.setup_dacs_2t_pulsing(&[(low, high, voltage)], true, true)?;
+        self.setup_dacs_2t_pulsing(&[(low, high, voltage)], true, true, unselected)?;
         self.add_delay(30_000u128)?;
         // HS configuration
         self.process(hsconf.compile())?;
@@ -2337,6 +5302,13 @@ impl Instrument {
 
     fn pulse_slice_slow(&mut self, chan: usize, voltage: f32, nanos: u128, mask: Option<&[usize]>) ->
         Result<&mut Self, ArC2Error> {
+        self._pulse_slice_slow_biased(chan, voltage, nanos, mask, &[])
+    }
+
+    /// Same as [`Instrument::pulse_slice_slow`] while holding the `unselected` channels
+    /// at the specified voltages.
+    fn _pulse_slice_slow_biased(&mut self, chan: usize, voltage: f32, nanos: u128,
+        mask: Option<&[usize]>, unselected: &[(usize, f32)]) -> Result<&mut Self, ArC2Error> {
 
         let mut bias_conf = ChannelConf::new();
 
@@ -2376,7 +5348,7 @@ impl Instrument {
         self.process(conf.compile())?;
 
         // setup a non-high speed differential pulsing scheme
-        self.setup_dacs_2t_pulsing(&channel_pairs, false, true)?;
+        self.setup_dacs_2t_pulsing(&channel_pairs, false, true, unselected)?;
         self.add_delay(nanos+30_000u128)?;
 
         Ok(self)
@@ -2384,6 +5356,13 @@ impl Instrument {
 
     fn pulse_slice_fast(&mut self, chan: usize, voltage: f32, nanos: u128, mask: Option<&[usize]>) ->
         Result<&mut Self, ArC2Error> {
+        self._pulse_slice_fast_biased(chan, voltage, nanos, mask, &[])
+    }
+
+    /// Same as [`Instrument::pulse_slice_fast`] while holding the `unselected` channels
+    /// at the specified voltages.
+    fn _pulse_slice_fast_biased(&mut self, chan: usize, voltage: f32, nanos: u128,
+        mask: Option<&[usize]>, unselected: &[(usize, f32)]) -> Result<&mut Self, ArC2Error> {
 
         let mut bias_conf = ChannelConf::new();
 
@@ -2468,7 +5447,7 @@ impl Instrument {
         // WARNING! If a non-differential pulse (last argument is `false`)
         // is used instead `timings` for high channels above should be
         // set to 0 ns.
-        self.setup_dacs_2t_pulsing(&channel_pairs, true, true)?;
+        self.setup_dacs_2t_pulsing(&channel_pairs, true, true, unselected)?;
         self.add_delay(30_000u128)?;
 
         self.process(hsconf.compile())?;
@@ -2875,20 +5854,344 @@ impl Instrument {
         Ok(self)
     }
 
-    /// Configure the digital GPIOs to the specified levels and direction.
-    /// GPIO direction can only be set on a per-cluster level (0–7, 8–15,
-    /// 16–23, 24–32).
-    pub fn set_logic(&mut self, cl0: IODir, cl1: IODir, cl2: IODir, cl3: IODir, mask: &IOMask) -> Result<&mut Self, ArC2Error> {
+    /// Configure the digital GPIOs to the specified levels and direction.
+    /// GPIO direction can only be set on a per-cluster level (0–7, 8–15,
+    /// 16–23, 24–32).
+    pub fn set_logic(&mut self, cl0: IODir, cl1: IODir, cl2: IODir, cl3: IODir, mask: &IOMask) -> Result<&mut Self, ArC2Error> {
+
+        let mut instr = UpdateLogic::with_directions(cl0, cl1, cl2, cl3, &mask);
+        self.process(instr.compile())?;
+
+        Ok(self)
+    }
+
+    /// Generate an arbitrary waveform from sampled points
+    ///
+    /// This will apply the waveform described by `points` on all channel pairs in `pairs`
+    /// which follow the format `(low, high)`. Argument `points` contains `(time, voltage)`
+    /// tuples with time in nanoseconds from the start of the waveform, in ascending
+    /// order. Similar to reads the voltage is applied as `-voltage` on the low channel
+    /// while the high channel is held at 0.0 V. The voltage of each point is held until
+    /// the time of the next point; the last point marks the end of the waveform and its
+    /// voltage will remain applied afterwards. If the index of a point is included in
+    /// `reads` a current read will be performed on all high channels as soon as the
+    /// voltage of that point is applied and the result will be added to the output
+    /// buffer. Indices exceeding the number of points are ignored. Use
+    /// [`Instrument::pick_one`] to retrieve the values. Consecutive points at the same
+    /// voltage are merged.
+    ///
+    /// Points are compiled into DAC configuration instructions followed by delays and,
+    /// as such, durations are rounded down to 20 ns. Reads wait for the DACs to settle
+    /// (30 μs) if the voltage has just changed and take a further 1 μs to complete;
+    /// these are subtracted from the hold time of the point, so points with reads
+    /// should be held for at least that long. The configuration of the DACs also takes
+    /// some time to complete which is not compensated for, so waveforms should not be
+    /// sampled faster than a few μs. Similar to [`Instrument::generate_ramp`] this needs
+    /// to be followed by an [`Instrument::execute`].
+    ///
+    /// ```no_run
+    /// use libarc2::{Instrument};
+    /// # use libarc2::ArC2Error;
+    ///
+    /// # fn main() -> Result<(), ArC2Error> {
+    /// let mut arc2 = Instrument::open_with_fw(0, "fw.bin", true, true).unwrap();
+    ///
+    /// // A 1.0 V triangular waveform, 400 μs long, with a read at the top
+    /// let points: Vec<(u128, f32)> = vec![(0, 0.0), (100_000, 0.5), (200_000, 1.0),
+    ///     (300_000, 0.5), (400_000, 0.0)];
+    /// arc2.generate_waveform(&[(3, 19)], &points, &[2])?.execute()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn generate_waveform(&mut self, pairs: &[(usize, usize)], points: &[(u128, f32)],
+        reads: &[usize]) -> Result<&mut Self, ArC2Error> {
+
+        // Check that the waveform is well-formed before issuing anything
+        for (idx, (t, v)) in points.iter().enumerate() {
+            if v.is_nan() {
+                return Err(ArC2Error::InvalidValue(*v));
+            }
+            if idx > 0 && *t <= points[idx-1].0 {
+                return Err(ArC2Error::WaveformTimingError(*t));
+            }
+        }
+
+        let lows: Vec<usize> = pairs.iter().map(|p| p.0).collect();
+        let highs: Vec<usize> = pairs.iter().map(|p| p.1).collect();
+        let reads: HashSet<usize> = reads.iter().cloned().collect();
+
+        // Set all involved channels to VoltArb once; the rest of the
+        // waveform is only DAC updates, reads and delays
+        let mut chans = lows.clone();
+        chans.extend_from_slice(&highs);
+        self.amp_prep(Some(&chans))?;
+
+        let mut channelstates = ChannelConf::new_with_state(ChannelState::Maintain);
+        for c in &chans {
+            channelstates.set(*c, ChannelState::VoltArb);
+        }
+        let mut channelconf = UpdateChannel::from_regs_default_source(&channelstates);
+        self.process(channelconf.compile())?;
+
+        let adcmask = ChanMask::from_channels(&highs);
+        let sender = self._sender.clone();
+
+        // time and voltage of the last point actually issued
+        let mut last: Option<(u128, f32)> = None;
+        // delays already spent on reads since the last point
+        let mut spent: u128 = 0;
+
+        for (idx, (t, v)) in points.iter().enumerate() {
+
+            let changed = last.map_or(true, |(_, prev)| prev != *v);
+            let read = reads.contains(&idx);
+
+            // Same voltage and no read; just keep on holding
+            if !changed && !read {
+                continue;
+            }
+
+            if let Some((prev_t, _)) = last {
+                let hold = (*t - prev_t).saturating_sub(spent);
+                if hold > 0u128 {
+                    self.add_delay(hold)?;
+                }
+            }
+            spent = 0;
+
+            if changed {
+                let mut input: Vec<(u16, u16, u16)> = lows.iter()
+                    .map(|c| (*c as u16, vidx!(-v), vidx!(-v)))
+                    .collect();
+                // high channels are only set once at the beginning
+                if last.is_none() {
+                    for c in &highs {
+                        input.push((*c as u16, vidx!(0.0), vidx!(0.0)));
+                    }
+                }
+                // channels are already at VoltArb; only the DACs are required
+                let (_, setdacs) = SetDAC::from_channels(&input, None,
+                    &ChannelState::Maintain, &ChannelState::Maintain)?;
+                for mut i in setdacs {
+                    self.process(i.compile())?;
+                }
+                self.process(&*UPDATE_DAC)?;
+            }
+
+            if read {
+                // let the DACs settle before reading
+                if changed {
+                    self.add_delay(30_000u128)?;
+                    spent += 30_000u128;
+                }
+
+                let chunk = self.make_chunk()?;
+
+                #[cfg(feature="zero_before_write")]
+                match self._zero_chunk(&chunk) {
+                    Ok(()) => {},
+                    Err(err) => { eprintln!("Zeroing chunk at {} failed: {}", chunk.addr(), err) }
+                };
+
+                let mut currentread = CurrentRead::new(&adcmask, chunk.addr(),
+                    chunk.flag_addr(), VALUEAVAILFLAG);
+                self.process(currentread.compile())?;
+                self.add_delay(1_000u128)?;
+                spent += 1_000u128;
+
+                // Force an AMP PRP on the C READ channels to avoid spikes
+                // during range transitions; see `_read_biased_inner`.
+                let mut amp_prep = AmpPrep::new(&adcmask);
+                self.process(amp_prep.compile())?;
+
+                match sender.send(Some(chunk)) {
+                    Ok(()) => {},
+                    Err(err) => { return Err(ArC2Error::from(err)); }
+                }
+            }
+
+            last = Some((*t, *v));
+        }
+
+        Ok(self)
+    }
+
+    /// Generate an arbitrary waveform from a function
+    ///
+    /// This is similar to [`Instrument::generate_waveform`] but instead of a list of points
+    /// the voltage is determined by `func` which is sampled every `step_nanos` (rounded down
+    /// to 20 ns) from 0 up to and including `duration_nanos`. The function is called with the
+    /// time of each sample in nanoseconds. If `read_every` is greater than 0 a current read
+    /// will be performed every `read_every` samples, starting from the first one.
+    ///
+    /// ```no_run
+    /// use libarc2::{Instrument};
+    /// # use libarc2::ArC2Error;
+    ///
+    /// # fn main() -> Result<(), ArC2Error> {
+    /// let mut arc2 = Instrument::open_with_fw(0, "fw.bin", true, true).unwrap();
+    ///
+    /// // One period of a 1 kHz, 0.5 V sine sampled every 10 μs, read every 100 μs
+    /// arc2.generate_waveform_fn(&[(3, 19), (4, 20)], 1_000_000, 10_000,
+    ///     |t| 0.5 * (2.0 * std::f32::consts::PI * (t as f32) / 1e6).sin(), 10)?
+    ///     .execute()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn generate_waveform_fn<F>(&mut self, pairs: &[(usize, usize)], duration_nanos: u128,
+        step_nanos: u128, func: F, read_every: usize) -> Result<&mut Self, ArC2Error>
+        where F: Fn(u128) -> f32 {
+
+        // Align step to the 20 ns granularity of the delay instruction
+        let step = step_nanos - (step_nanos % 20);
+        if step == 0 {
+            return Err(ArC2Error::WaveformTimingError(step_nanos));
+        }
+
+        let nsamples = (duration_nanos / step) as usize + 1;
+        let mut points: Vec<(u128, f32)> = Vec::with_capacity(nsamples);
+        let mut reads: Vec<usize> = Vec::new();
+
+        for i in 0..nsamples {
+            let t = (i as u128) * step;
+            points.push((t, func(t)));
+            if read_every > 0 && i % read_every == 0 {
+                reads.push(i);
+            }
+        }
+
+        self.generate_waveform(pairs, &points, &reads)
+    }
+
+    pub fn generate_ramp(&mut self,
+        low: usize, high: usize,
+        vstart: f32, vstep: f32, vstop: f32,
+        pw_nanos: u128, inter_nanos: u128,
+        num_pulses: usize,
+        read_at: ReadAt, read_after: ReadAfter) ->
+        Result<&mut Self, ArC2Error> {
+
+        let voltages = RampShape::Linear.voltages(vstart, vstep, vstop, 1)?;
+        self._generate_ramp_inner(low, high, &voltages, pw_nanos, inter_nanos,
+            num_pulses, read_at, read_after)
+    }
+
+    /// Generate a shaped voltage ramp
+    ///
+    /// This is the same as [`Instrument::generate_ramp`] but the voltage profile is
+    /// determined by `shape` (see [`RampShape`] for the available profiles) and repeated
+    /// `cycles` times. All other arguments, including the read-out configuration, follow
+    /// the same conventions as [`Instrument::generate_ramp`]. When using
+    /// [`ReadAfter::Ramp`] a single read-out will be performed at the end of all cycles.
+    /// This also needs to be followed by an [`Instrument::execute`].
+    ///
+    /// ```no_run
+    /// use libarc2::{Instrument, RampShape, ReadAt, ReadAfter};
+    /// # use libarc2::ArC2Error;
+    ///
+    /// # fn main() -> Result<(), ArC2Error> {
+    /// let mut arc2 = Instrument::open_with_fw(0, "fw.bin", true, true).unwrap();
+    ///
+    /// // Two I–V loops 0 → 1.5 → 0 → -1.5 → 0 V in 50 mV steps, 100 μs pulses,
+    /// // read at bias after every pulse
+    /// arc2.generate_ramp_shaped(3, 19, RampShape::Bipolar, 0.0, 0.05, 1.5, 2,
+    ///     100_000, 0, 1, ReadAt::Bias, ReadAfter::Pulse)?
+    ///     .execute()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn generate_ramp_shaped(&mut self,
+        low: usize, high: usize,
+        shape: RampShape,
+        vstart: f32, vstep: f32, vstop: f32,
+        cycles: usize,
+        pw_nanos: u128, inter_nanos: u128,
+        num_pulses: usize,
+        read_at: ReadAt, read_after: ReadAfter) ->
+        Result<&mut Self, ArC2Error> {
+
+        let voltages = shape.voltages(vstart, vstep, vstop, cycles)?;
+        self._generate_ramp_inner(low, high, &voltages, pw_nanos, inter_nanos,
+            num_pulses, read_at, read_after)
+    }
+
+    /// Generate a pulse train with arbitrary amplitudes and widths
+    ///
+    /// This will apply a series of pulses on the crosspoint defined by `low` and `high`
+    /// as described by `pulses`, an iterator of `(voltage, width, interval)` tuples,
+    /// with width and interval in nanoseconds. Each pulse is followed by a wait of
+    /// `interval` ns, during which the crosspoint is grounded. For every pulse the
+    /// high-speed drivers will be used for pulses shorter than 500 ms and regular
+    /// arbitrary voltage pulses otherwise. If `read_every` is greater than 0 a current
+    /// read will be performed after every `read_every` pulses (before the interval
+    /// elapses) following the conventions of [`ReadAt`]; for [`ReadAt::Bias`] the
+    /// voltage of the last pulse is used. Results are added to the output buffer, use
+    /// [`Instrument::pick_one`] to retrieve them. Similar to
+    /// [`Instrument::generate_ramp`] this needs to be followed by an
+    /// [`Instrument::execute`].
+    ///
+    /// ```no_run
+    /// use libarc2::{Instrument, ReadAt};
+    /// # use libarc2::ArC2Error;
+    ///
+    /// # fn main() -> Result<(), ArC2Error> {
+    /// let mut arc2 = Instrument::open_with_fw(0, "fw.bin", true, true).unwrap();
+    ///
+    /// // 1.5 V pulses of increasing width, 100 ns → 100 μs, read at 200 mV
+    /// // after each one
+    /// let train = (0..4).map(|i| (1.5, 100u128 * 10u128.pow(i), 10_000u128));
+    /// arc2.generate_pulse_train(3, 19, train, ReadAt::Arb(0.2), 1)?
+    ///     .execute()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn generate_pulse_train<I>(&mut self, low: usize, high: usize, pulses: I,
+        read_at: ReadAt, read_every: usize) -> Result<&mut Self, ArC2Error>
+        where I: IntoIterator<Item=(f32, u128, u128)> {
+
+        let sender = self._sender.clone();
+        let do_reads = read_every > 0 && !read_at.is_never();
+
+        for (idx, (v, width, interval)) in pulses.into_iter().enumerate() {
+
+            if v.is_nan() {
+                return Err(ArC2Error::InvalidValue(v));
+            }
+
+            if width < 500_000_000u128 {
+                self.pulse_one_fast(low, high, v, width)?;
+            } else {
+                self.pulse_one_slow(low, high, v, width)?;
+            }
+
+            if interval > 0u128 {
+                self.ground_slice_fast(&[low, high])?;
+            }
 
-        let mut instr = UpdateLogic::with_directions(cl0, cl1, cl2, cl3, &mask);
-        self.process(instr.compile())?;
+            if do_reads && (idx + 1) % read_every == 0 {
+                let vread = match read_at {
+                    ReadAt::Bias => v,
+                    ReadAt::Arb(arbv) => arbv,
+                    ReadAt::Never => { unreachable!() }
+                };
+                let chunk = self._read_slice_inner(low, &[high], vidx!(-vread))?;
+                match sender.send(Some(chunk)) {
+                    Ok(()) => {},
+                    Err(err) => { return Err(ArC2Error::from(err)); }
+                }
+            }
+
+            if interval > 0u128 {
+                self.add_delay(interval)?;
+            }
+        }
 
         Ok(self)
     }
 
-    pub fn generate_ramp(&mut self,
+    fn _generate_ramp_inner(&mut self,
         low: usize, high: usize,
-        vstart: f32, vstep: f32, vstop: f32,
+        voltages: &[f32],
         pw_nanos: u128, inter_nanos: u128,
         num_pulses: usize,
         read_at: ReadAt, read_after: ReadAfter) ->
@@ -2926,19 +6229,9 @@ impl Instrument {
             Ok(())
         }
 
-        // Check if voltage ramp is described correctly
-        if vstop < vstart && vstep >= 0.0 {
-            return Err(ArC2Error::RampOperationError(vstart, vstop, vstep));
-        }
-
-        // determine the number of steps
-        let steps = f32::ceil((vstop - vstart)/vstep) as usize;
-
         let sender = self._sender.clone();
 
-        for idx in 0..steps {
-
-            let v = vstart + vstep*(idx as f32);
+        for v in voltages.iter().copied() {
 
             // if num pulses is 0 then no pulsing will be done, only reads
             if num_pulses == 0 {
@@ -3011,9 +6304,11 @@ impl Instrument {
 
         // if we are doing read after ramp, do it here as the ramp is finished now
         if read_after.is_at_ramp() {
-            // take the last value of the voltage list, lists are always [start, stop)
-            // so substract 1 from steps to get the last value
-            let voltage = vstart + vstep*((steps-1) as f32);
+            // take the last value of the voltage list
+            let voltage = match voltages.last() {
+                Some(v) => *v,
+                None => { return Ok(self); }
+            };
             let chunk = __do_read(self, low, high, &read_at, voltage)?;
             match sender.send(Some(chunk)) {
                 Ok(()) => {},
@@ -3024,6 +6319,108 @@ impl Instrument {
         Ok(self)
     }
 
+    /// Generate a current sweep on the specified channels
+    ///
+    /// This is the current-controlled equivalent of [`Instrument::generate_ramp`]. The
+    /// current source is stepped from `istart` to `istop` (exclusive) in steps of `istep`
+    /// and at every step the current is held for `dwell_nanos`. If `vread` is `true` a
+    /// voltage read will be performed on `chans` at the end of every step and added to
+    /// the output buffer; use [`Instrument::pick_one`] with [`ReadType::Voltage`] to
+    /// retrieve the values. For every step the current source is reconfigured through
+    /// [`SourceConf::for_current`][`crate::registers::SourceConf::for_current`] and the
+    /// series resistors of the current source (or the digipot) are switched automatically
+    /// as the current crosses decades. Steps at exactly 0 A are done with the current
+    /// source disconnected. As with [`Instrument::config_current_source`] the static end
+    /// of the DUTs must be configured beforehand and must not be reconfigured during the
+    /// sweep. The current source is disconnected at the end of the sweep. Similar to
+    /// [`Instrument::generate_ramp`] this needs to be followed by an
+    /// [`Instrument::execute`].
+    ///
+    /// ```no_run
+    /// use libarc2::{Instrument, ReadType, DataMode};
+    /// # use libarc2::ArC2Error;
+    ///
+    /// # fn main() -> Result<(), ArC2Error> {
+    /// let mut arc2 = Instrument::open_with_fw(0, "fw.bin", true, true).unwrap();
+    ///
+    /// // Hold channel 3 at 0.0 V and sweep from 1 μA to 100 μA through channel 19
+    /// arc2.config_channels(&[(3, 0.0)], None)?
+    ///     .generate_current_ramp(&[19], 1e-6, 1e-6, 100e-6, 1_000_000u128, true)?
+    ///     .execute()?;
+    ///
+    /// while let Some(v) = arc2.pick_one(DataMode::All, ReadType::Voltage)? {
+    ///     println!("Voltage: {} V", v[19]);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn generate_current_ramp(&mut self, chans: &[usize], istart: f32, istep: f32,
+        istop: f32, dwell_nanos: u128, vread: bool) -> Result<&mut Self, ArC2Error> {
+
+        // Check if current ramp is described correctly
+        if (istop < istart && istep >= 0.0) || (istop > istart && istep <= 0.0) {
+            return Err(ArC2Error::RampOperationError(istart, istop, istep));
+        }
+
+        // determine the number of steps
+        let steps = f32::ceil((istop - istart)/istep) as usize;
+
+        // validate all currents before issuing any instructions
+        for idx in 0..steps {
+            let current = istart + istep*(idx as f32);
+            if current != 0.0 && SourceConf::for_current(current).is_none() {
+                return Err(ArC2Error::InvalidValue(current));
+            }
+        }
+
+        let mut sorted = chans.to_vec();
+        sorted.sort();
+
+        let sender = self._sender.clone();
+
+        // Current source state (MOSFET and series resistor) of the previous
+        // step; if that changes the source range must be reconfigured
+        let mut prev_state: Option<u8> = None;
+
+        for idx in 0..steps {
+
+            let current = istart + istep*(idx as f32);
+
+            if current == 0.0 {
+                self.connect_to_current_source(&[])?;
+            } else {
+                // unwrap is safe here as all currents have been validated above
+                let (conf, _) = SourceConf::for_current(current).unwrap();
+                let state = conf.get_cursource_state().to_u8();
+                let digipot = (state & CurrentSourceResistor::RDIGI.bits()) > 0;
+
+                // Range must be set if this is the first step, the series resistor
+                // has changed or the digipot is used (as its value changes with
+                // the current).
+                let preset = digipot || prev_state.map_or(true, |s| s != state);
+                self.config_current_source(chans, current, prev_state.is_none(), preset)?;
+
+                prev_state = Some(state);
+            }
+
+            if dwell_nanos > 0u128 {
+                self.add_delay(dwell_nanos)?;
+            }
+
+            if vread {
+                let chunk = self._vread_channels_deferred_chunk(&sorted, true)?;
+                match sender.send(Some(chunk)) {
+                    Ok(()) => {},
+                    Err(err) => { return Err(ArC2Error::from(err)); }
+                }
+            }
+        }
+
+        self.connect_to_current_source(&[])?;
+
+        Ok(self)
+    }
+
     /// Perform a retention-like read train operation on a single cross-point at specific
     /// interpulse intervals.
     ///
@@ -3037,86 +6434,541 @@ impl Instrument {
     /// retention-like measurement (read an otherwise grounded cross-point at specific intervals)
     /// then the correct call is `read_train(low, high, vread, interpulse, None,
     /// WaitFor.Time(…)/WaitFor.Iterations(…))`.
-    // XXX: This needs better in-thread error handling
+    ///
+    /// The operation runs on a background thread; use the returned [`OpHandle`] to cancel
+    /// it, query its progress or wait for it to finish. If the operation is cancelled or
+    /// fails the crosspoint is grounded regardless of `preload`.
     pub fn read_train(&mut self, low: usize, high: usize, vread: f32, interpulse: u128,
         preload: Option<f32>, condition: WaitFor)
-        -> Result<(), ArC2Error> {
+        -> Result<OpHandle, ArC2Error> {
 
-        self._op_running.store(true, atomic::Ordering::Relaxed);
         self.reset_dacs()?;
 
+        let total = match condition {
+            WaitFor::Iterations(i) => Some(i),
+            WaitFor::Time(_) => None
+        };
+
+        Ok(self._spawn_op(total, move |slf, progress, cancel| {
+            slf._read_train_inner(low, high, vread, interpulse, preload, condition, progress,
+                cancel)
+        }))
+    }
+
+    fn _read_train_inner(&mut self, low: usize, high: usize, vread: f32, interpulse: u128,
+        preload: Option<f32>, condition: WaitFor, progress: &atomic::AtomicUsize,
+        cancel: &atomic::AtomicBool) -> Result<(), ArC2Error> {
+
         let now = time::Instant::now();
         let mut iter = 0;
+        let sender = self._sender.clone();
 
-        let mut slf = self.clone();
-        let cond = condition.clone();
+        // if preloading, set the bias voltage, this will get
+        // executed *before* the first execute, implicitly called by
+        // the first _read_slice_inner of the loop
+        match preload {
+            Some(v) => {
+                let input: Vec<(u16, f32)> = vec![(low as u16, -v)];
+                self.config_channels(&input, None)?;
+            }
+            _ => {}
+        };
 
-        std::thread::spawn(move || {
-            let sender = slf._sender.clone();
+        loop {
+            // grounding is done by the caller
+            if cancel.load(atomic::Ordering::Relaxed) {
+                break;
+            }
 
-            // if preloading, set the bias voltage, this will get
-            // executed *before* the first execute, implicitly called by
-            // the first _read_slice_inner of the loop
+            let chunk = self._read_slice_inner(low, &[high], vidx!(-vread))?;
+            // if crosspoint is preloaded do not ground the lines, instead put it
+            // back to the preloading voltage (if different from vread)
             match preload {
                 Some(v) => {
-                    let input: Vec<(u16, f32)> = vec![(low as u16, -v)];
-                    slf.config_channels(&input, None).unwrap();
+                    // unless preload and vread are similar (difference is less
+                    // than 5 mV) put the correct bias back on the line
+                    if (vread == v) || (vread - v).abs() > 5e-3 {
+                        let input: Vec<(u16, f32)> = vec![(low as u16, -v)];
+                        self.config_channels(&input, None)?
+                            .execute()?;
+                    }
                 }
-                _ => {}
+                None => { self.ground_all_fast()?; }
+            };
+            if interpulse > 0u128 {
+                self.add_delay(interpulse)?;
+            }
+            self.execute()?;
+            self.wait();
+            sender.send(Some(chunk))?;
+            iter += 1;
+            progress.store(iter, atomic::Ordering::Relaxed);
+
+            let finished = match condition {
+                WaitFor::Time(d) => now.elapsed() >= d,
+                WaitFor::Iterations(i) => iter >= i
             };
 
-            loop {
-                let chunk = slf._read_slice_inner(low, &[high], vidx!(-vread)).unwrap();
-                // if crosspoint is preloaded do not ground the lines, instead put it
-                // back to the preloading voltage (if different from vread)
+            if finished {
+                // ensure crosspoint is grounded if not pre-loaded
                 match preload {
-                    Some(v) => {
-                        // unless preload and vread are similar (difference is less
-                        // than 5 mV) put the correct bias back on the line
-                        if (vread == v) || (vread - v).abs() > 5e-3 {
-                            let input: Vec<(u16, f32)> = vec![(low as u16, -v)];
-                            slf.config_channels(&input, None).unwrap()
-                               .execute().unwrap();
-                        }
-                    }
-                    None => { slf.ground_all_fast().unwrap(); }
+                    None => { self.ground_all_fast()?; }
+                    _ => {}
                 };
-                if interpulse > 0u128 {
-                    slf.add_delay(interpulse).unwrap();
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run `op` on a background thread. This takes care of the state of the
+    /// instrument (running and cancel flags) and ensures all channels are
+    /// grounded if the operation is cancelled, fails or panics. `total` is the
+    /// expected number of steps of the operation, if known. `op` receives its
+    /// progress counter and its own cancellation flag, which it should check
+    /// regularly.
+    fn _spawn_op<F>(&mut self, total: Option<usize>, op: F) -> OpHandle
+        where F: FnOnce(&mut Instrument, &atomic::AtomicUsize, &atomic::AtomicBool)
+            -> Result<(), ArC2Error> + Send + 'static {
+
+        let cancel = Arc::new(atomic::AtomicBool::new(false));
+        {
+            let mut ops = self._op_cancel.lock().unwrap_or_else(|e| e.into_inner());
+            ops.push(cancel.clone());
+            self._op_running.store(true, atomic::Ordering::Relaxed);
+        }
+
+        let progress = Arc::new(atomic::AtomicUsize::new(0));
+        let mut slf = self.clone();
+        let thread_progress = progress.clone();
+        let thread_cancel = cancel.clone();
+
+        let thread = std::thread::spawn(move || {
+            let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                op(&mut slf, &thread_progress, &thread_cancel)
+            }));
+
+            // best effort; the original error or panic takes precedence
+            let failed = !matches!(res, Ok(Ok(())));
+            let ground = if failed || thread_cancel.load(atomic::Ordering::Relaxed) {
+                std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    slf.ground_all().and_then(|s| s.execute()).map(|_| ())
+                }))
+            } else {
+                Ok(Ok(()))
+            };
+
+            // deregister the operation; the instrument is idle once the
+            // last one is gone
+            {
+                let mut ops = slf._op_cancel.lock().unwrap_or_else(|e| e.into_inner());
+                ops.retain(|c| !Arc::ptr_eq(c, &thread_cancel));
+                if ops.is_empty() {
+                    slf._op_running.store(false, atomic::Ordering::Relaxed);
                 }
-                slf.execute().unwrap();
-                slf.wait();
-                sender.send(Some(chunk)).unwrap();
-                iter += 1;
-
-                match cond {
-                    WaitFor::Time(d) => {
-                        if now.elapsed() >= d {
-                            // ensure crosspoint is grounded if pre-loaded
-                            match preload {
-                                None => { slf.ground_all_fast().unwrap(); }
-                                _ => {}
-                            };
-                            break;
-                        }
-                    },
-                    WaitFor::Iterations(i) => {
-                        if iter >= i {
-                            // ensure crosspoint is grounded if pre-loaded
-                            match preload {
-                                None => { slf.ground_all_fast().unwrap(); }
-                                _ => {}
-                            };
-                            break;
-                        }
+            }
+
+            match (res, ground) {
+                (Err(panic), _) | (Ok(_), Err(panic)) => std::panic::resume_unwind(panic),
+                (Ok(res), Ok(ground)) => res.and(ground)
+            }
+        });
+
+        OpHandle {
+            _thread: Some(thread),
+            _cancel: cancel,
+            _progress: progress,
+            _total: total
+        }
+    }
+
+    /// Perform an endurance test on a single crosspoint
+    ///
+    /// This will alternate between SET and RESET pulses on the crosspoint defined by `low`
+    /// and `high` for the specified number of `cycles`. Pulses are given as `(voltage,
+    /// width)` tuples and follow the conventions of [`Instrument::pulse_one`]; pulses
+    /// shorter than 500 ms are applied with the high speed drivers. If `read_every` is
+    /// greater than 0 the crosspoint will be read at `vread` after the SET and after
+    /// the RESET pulse of every `read_every`-th cycle and both readouts will be added to
+    /// the output buffer, LRS first, for retrieval with [`Instrument::pick_one`]. If
+    /// `min_window` is not `None` the test will stop early as soon as the ratio of the
+    /// HRS to the LRS resistance drops below it, which typically signifies device
+    /// failure. The operation runs on a background thread, similar to
+    /// [`Instrument::read_train`], and can be stopped at any time through the returned
+    /// [`OpHandle`]. The crosspoint is grounded when the test finishes.
+    ///
+    /// ```no_run
+    /// use libarc2::{Instrument, DataMode, ReadType};
+    /// # use libarc2::ArC2Error;
+    ///
+    /// # fn main() -> Result<(), ArC2Error> {
+    /// let mut arc2 = Instrument::open_with_fw(0, "fw.bin", true, true).unwrap();
+    ///
+    /// // 10⁶ cycles with 1 μs pulses, reading every 1000 cycles and
+    /// // stopping if HRS/LRS falls below 2
+    /// let handle = arc2.endurance(3, 19, (1.5, 1_000), (-2.0, 1_000), 1_000_000, 1_000,
+    ///     0.2, Some(2.0))?;
+    ///
+    /// while let Some(lrs) = arc2.pick_one(DataMode::All, ReadType::Current)? {
+    ///     let hrs = arc2.pick_one(DataMode::All, ReadType::Current)?.unwrap();
+    ///     println!("LRS: {} A, HRS: {} A", lrs[19], hrs[19]);
+    /// }
+    ///
+    /// handle.join()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn endurance(&mut self, low: usize, high: usize, set_pulse: (f32, u128),
+        reset_pulse: (f32, u128), cycles: usize, read_every: usize, vread: f32,
+        min_window: Option<f32>) -> Result<OpHandle, ArC2Error> {
+
+        for v in &[set_pulse.0, reset_pulse.0, vread] {
+            if v.is_nan() {
+                return Err(ArC2Error::InvalidValue(*v));
+            }
+        }
+
+        self.reset_dacs()?;
+
+        Ok(self._spawn_op(Some(cycles), move |slf, progress, cancel| {
+            slf._endurance_inner(low, high, set_pulse, reset_pulse, cycles,
+                read_every, vread, min_window, progress, cancel)?;
+            slf.ground_all()?.execute()?;
+            Ok(())
+        }))
+    }
+
+    fn _endurance_inner(&mut self, low: usize, high: usize, set_pulse: (f32, u128),
+        reset_pulse: (f32, u128), cycles: usize, read_every: usize, vread: f32,
+        min_window: Option<f32>, progress: &atomic::AtomicUsize, cancel: &atomic::AtomicBool)
+        -> Result<(), ArC2Error> {
+
+        // if no reads are required the instrument is still synchronised
+        // every few cycles so that the test can be cancelled
+        let batch = if read_every > 0 { read_every } else { 1_000 };
+        let sender = self._sender.clone();
+        let mut cycle = 0;
+
+        fn __pulse(slf: &mut Instrument, low: usize, high: usize, pulse: (f32, u128))
+            -> Result<(), ArC2Error> {
+            if pulse.1 < 500_000_000u128 {
+                slf.pulse_one_fast(low, high, pulse.0, pulse.1)?;
+            } else {
+                slf.pulse_one_slow(low, high, pulse.0, pulse.1)?;
+            }
+            Ok(())
+        }
+
+        while cycle < cycles {
+
+            if cancel.load(atomic::Ordering::Relaxed) {
+                break;
+            }
+
+            let n = batch.min(cycles - cycle);
+            let read = read_every > 0 && n == read_every;
+
+            // all but the last cycle of the batch are only pulses
+            for _ in 0..(n-1) {
+                __pulse(self, low, high, set_pulse)?;
+                __pulse(self, low, high, reset_pulse)?;
+            }
+
+            let chunks = if read {
+                __pulse(self, low, high, set_pulse)?;
+                self.ground_all_fast()?;
+                let lrs = self._read_slice_inner(low, &[high], vidx!(-vread))?;
+                self.ground_all_fast()?;
+                __pulse(self, low, high, reset_pulse)?;
+                self.ground_all_fast()?;
+                let hrs = self._read_slice_inner(low, &[high], vidx!(-vread))?;
+                self.ground_all_fast()?;
+                Some((lrs, hrs))
+            } else {
+                __pulse(self, low, high, set_pulse)?;
+                __pulse(self, low, high, reset_pulse)?;
+                None
+            };
+
+            self.execute()?;
+            self.wait();
+            cycle += n;
+            progress.store(cycle, atomic::Ordering::Relaxed);
+
+            if let Some((lrs, hrs)) = chunks {
+                // peek at the values before handing the chunks over to
+                // the output buffer
+                let ilrs = self.currents_from_address(lrs.addr(), &[high])?[high];
+                let ihrs = self.currents_from_address(hrs.addr(), &[high])?[high];
+
+                for chunk in vec![lrs, hrs] {
+                    match sender.send(Some(chunk)) {
+                        Ok(()) => {},
+                        Err(err) => { return Err(ArC2Error::from(err)); }
+                    }
+                }
+
+                if let Some(window) = min_window {
+                    // R_HRS/R_LRS is I_LRS/I_HRS as reads are done at the same voltage
+                    if (ilrs / ihrs).abs() < window {
+                        break;
                     }
                 }
             }
-            slf._op_running.store(false, atomic::Ordering::Relaxed);
-        });
+        }
 
         Ok(())
+    }
+
+    /// Perform a retention test on multiple crosspoints
+    ///
+    /// This will read all `crosspoints`, given as `(low, high)` tuples, at `vread` at the
+    /// times specified by `schedule` (counted from the start of the operation; see
+    /// [`log_schedule`] for a common logarithmic schedule). At every scheduled time the
+    /// crosspoints are read in turn as with [`Instrument::read_one`]. If reads fall behind
+    /// schedule the next reads follow immediately. Between scheduled times the host thread
+    /// sleeps and the crosspoints are grounded, so no long delays are queued on the
+    /// instrument. The operation runs on a background thread, similar to
+    /// [`Instrument::read_train`], and is controlled through the returned [`OpHandle`].
+    ///
+    /// Results are not added to the output buffer; instead they are delivered through
+    /// the returned receiver as `(crosspoint index, elapsed time, current)` tuples, where
+    /// index refers to the position of the crosspoint in `crosspoints` and elapsed time
+    /// is the moment the read was issued. The receiver disconnects once the test is
+    /// finished, and dropping it stops the test.
+    ///
+    /// ```no_run
+    /// use libarc2::{Instrument, log_schedule};
+    /// use std::time::Duration;
+    /// # use libarc2::ArC2Error;
+    ///
+    /// # fn main() -> Result<(), ArC2Error> {
+    /// let mut arc2 = Instrument::open_with_fw(0, "fw.bin", true, true).unwrap();
+    ///
+    /// // Read two crosspoints at 1 s, 2 s, 5 s, 10 s, … up to 3 hours
+    /// let schedule = log_schedule(Duration::from_secs(1), Duration::from_secs(3*3600),
+    ///     &[1.0, 2.0, 5.0]);
+    /// let (handle, results) = arc2.retention(&[(3, 19), (4, 20)], &schedule, 0.2)?;
+    ///
+    /// for (idx, elapsed, current) in results {
+    ///     println!("{} {:?} {}", idx, elapsed, 0.2/current);
+    /// }
+    ///
+    /// handle.join()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn retention(&mut self, crosspoints: &[(usize, usize)], schedule: &[time::Duration],
+        vread: f32) -> Result<(OpHandle, Receiver<(usize, time::Duration, f32)>), ArC2Error> {
+
+        if vread.is_nan() {
+            return Err(ArC2Error::InvalidValue(vread));
+        }
+
+        let (sender, receiver) = channel::<(usize, time::Duration, f32)>();
+
+        self.ground_all()?.execute()?;
+
+        let crosspoints = crosspoints.to_vec();
+        let mut schedule = schedule.to_vec();
+        schedule.sort();
+
+        let handle = self._spawn_op(Some(schedule.len()), move |slf, progress, cancel| {
+            let now = time::Instant::now();
+
+            'schedule: for (step, t) in schedule.iter().enumerate() {
+
+                // sleep in short slices so that the test can be cancelled
+                while now.elapsed() < *t {
+                    if cancel.load(atomic::Ordering::Relaxed) {
+                        break 'schedule;
+                    }
+                    let remaining = t.saturating_sub(now.elapsed());
+                    spin_sleep::sleep(remaining.min(time::Duration::from_millis(100)));
+                }
+
+                for (idx, (low, high)) in crosspoints.iter().enumerate() {
+                    if cancel.load(atomic::Ordering::Relaxed) {
+                        break 'schedule;
+                    }
+
+                    let elapsed = now.elapsed();
+                    let current = slf.read_one(*low, *high, vread)?;
+
+                    // receiver is gone; nobody is listening
+                    if sender.send((idx, elapsed, current)).is_err() {
+                        break 'schedule;
+                    }
+                }
+
+                progress.store(step + 1, atomic::Ordering::Relaxed);
+            }
+
+            slf.ground_all()?.execute()?;
+            Ok(())
+        });
+
+        Ok((handle, receiver))
+    }
+
+    /// Run an arbitrary acquisition on a background thread
+    ///
+    /// This is a generic runner for experiments of arbitrary length. Instead of queueing
+    /// all instructions before calling [`Instrument::execute`] the experiment is described
+    /// by `generator` which is called repeatedly with an increasing step number, starting
+    /// from 0. On every call it should queue the instructions of one step, which may add
+    /// results to the output buffer (for instance by calling
+    /// [`Instrument::generate_read_train`] or [`Instrument::read_biased_deferred`]), and
+    /// return `Ok(true)` or, when the experiment is over, return `Ok(false)` without
+    /// queueing anything. Steps are accumulated until about one FIFO worth of
+    /// instructions has been queued and then they are executed; results are retrieved
+    /// as soon as they become available, converted according to `mode` and `rtype` and
+    /// passed on to `consumer` in the order they were generated. If `consumer` returns
+    /// `false` the acquisition stops. Generation of new steps is also suspended whenever
+    /// the instrument is low on memory until enough results have been consumed. This
+    /// way only a small portion of the experiment is held in memory at any given time.
+    ///
+    /// The acquisition is controlled through the returned [`OpHandle`] and its progress
+    /// is the number of steps generated. The runner takes ownership of the output buffer
+    /// so [`Instrument::pick_one`] should not be used until it finishes, and the output
+    /// buffer should be empty when it starts. Retained mode is recommended; in immediate
+    /// mode every step is synchronised separately. Channels are not grounded at the end
+    /// of the acquisition unless the generator does so, but they are if the
+    /// acquisition is cancelled or fails.
+    ///
+    /// ```no_run
+    /// use libarc2::{Instrument, DataMode, ReadType};
+    /// # use libarc2::ArC2Error;
+    ///
+    /// # fn main() -> Result<(), ArC2Error> {
+    /// let mut arc2 = Instrument::open_with_fw(0, "fw.bin", true, true).unwrap();
+    ///
+    /// // Read crosspoint 3 × 19 every 100 μs, forever, and print the values
+    /// let handle = arc2.run_acquisition(|arc2, _| {
+    ///     arc2.generate_read_train(&[3], &[19], 0.2, 100, 100_000, true)?;
+    ///     Ok(true)
+    /// }, |values| {
+    ///     println!("{}", values[19]);
+    ///     true
+    /// }, DataMode::All, ReadType::Current)?;
+    ///
+    /// std::thread::sleep(std::time::Duration::from_secs(60));
+    /// handle.cancel();
+    /// handle.join()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn run_acquisition<G, C>(&mut self, mut generator: G, mut consumer: C,
+        mode: DataMode, rtype: ReadType) -> Result<OpHandle, ArC2Error>
+        where G: FnMut(&mut Instrument, usize) -> Result<bool, ArC2Error> + Send + 'static,
+              C: FnMut(Vec<f32>) -> bool + Send + 'static {
+
+        Ok(self._spawn_op(None, move |slf, progress, cancel| {
+
+            let mut step = 0;
+            let mut exhausted = false;
+
+            while !exhausted {
+
+                if cancel.load(atomic::Ordering::Relaxed) {
+                    break;
+                }
+
+                // Queue steps until there is enough to keep the FIFO busy
+                // or memory is running out; at least one step is always
+                // queued as all previous results have been consumed
+                let first = step;
+                loop {
+                    let queued = match slf.instr_buffer {
+                        Some(ref buf) => buf.read().unwrap().len(),
+                        None => INSTRCAP
+                    };
+                    let free = slf.memman.read().unwrap().free_blocks();
+
+                    if step > first && (queued >= INSTRCAP || free < ACQMINFREE) {
+                        break;
+                    }
+
+                    if !generator(slf, step)? {
+                        exhausted = true;
+                        break;
+                    }
+
+                    step += 1;
+                    progress.store(step, atomic::Ordering::Relaxed);
+                }
+
+                slf.execute()?;
+                slf.wait();
+
+                if !slf._drain_output(&mode, &rtype, &mut consumer)? {
+                    break;
+                }
+            }
+
+            Ok(())
+        }))
+    }
+
+    /// Run an arbitrary acquisition on a background thread delivering results to a channel
+    ///
+    /// This is the same as [`Instrument::run_acquisition`] but instead of a consumer
+    /// callback results are delivered through the returned receiver. The acquisition stops
+    /// if the receiver is dropped.
+    pub fn run_acquisition_channel<G>(&mut self, generator: G, mode: DataMode,
+        rtype: ReadType) -> Result<(OpHandle, Receiver<Vec<f32>>), ArC2Error>
+        where G: FnMut(&mut Instrument, usize) -> Result<bool, ArC2Error> + Send + 'static {
+
+        let (sender, receiver) = channel::<Vec<f32>>();
+        let handle = self.run_acquisition(generator, move |values| {
+            sender.send(values).is_ok()
+        }, mode, rtype)?;
+
+        Ok((handle, receiver))
+    }
+
+    /// Pass all available results from the output buffer to `consumer`. Returns
+    /// `false` if the consumer refused any of them.
+    fn _drain_output<C>(&mut self, mode: &DataMode, rtype: &ReadType, consumer: &mut C)
+        -> Result<bool, ArC2Error>
+        where C: FnMut(Vec<f32>) -> bool {
+
+        loop {
+            let chunk_opt = {
+                let _receiver = self._receiver.clone();
+                let receiver = _receiver.lock().unwrap();
+                match receiver.try_recv() {
+                    Ok(x) => x,
+                    Err(_) => { return Ok(true); }
+                }
+            };
+
+            let mut chunk = match chunk_opt {
+                Some(c) => c,
+                None => { continue; }
+            };
 
+            #[cfg(feature="flag_addresses")]
+            self.wait_for_flag(&chunk)?;
+
+            let values = self.read_chunk(&mut chunk, mode, rtype)?;
+            if !consumer(values) {
+                return Ok(false);
+            }
+        }
+    }
+
+    /// Request cancellation of all currently running background operations, such as
+    /// [`Instrument::read_train`], [`Instrument::endurance`] or [`Instrument::retention`].
+    /// The operations will stop at the earliest opportunity and ground all channels.
+    /// Results already added to the output buffer remain available. Use
+    /// [`OpHandle::cancel`] to cancel a single operation.
+    pub fn cancel_op(&self) {
+        let ops = self._op_cancel.lock().unwrap_or_else(|e| e.into_inner());
+        for cancel in ops.iter() {
+            cancel.store(true, atomic::Ordering::Relaxed);
+        }
     }
 
     /// Read one block of values from the internal buffer
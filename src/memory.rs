@@ -205,6 +205,11 @@ impl MemMan {
         Ok(chunk)
     }
 
+    /// Number of chunks that can still be allocated.
+    pub fn free_blocks(&self) -> usize {
+        self.free_blocks
+    }
+
     /// Release the address represented by a [`Chunk`] back into the
     /// available memory pool.
     pub fn free_chunk(&mut self, chunk: &mut Chunk) -> Result<(), MemoryError> {
@@ -1429,8 +1429,8 @@ impl SourceConf {
         // If we reach this point it means we need to use the digipot instead of
         // the fixed resistors
         match Self::_find_digipot_and_voltage(current) {
-            Some ((resistance, voltage)) => {
-                let code = (1024.0*(1.0 - resistance/20000.0)).floor() as u16;
+            Some ((voltage, resistance)) => {
+                let code = ((1024.0*(1.0 - resistance/20000.0)).floor() as u16).min(0x3ff);
                 let state = CurrentSourceState::mos_with_resistors(mosfet,
                     CurrentSourceResistor::RDIGI);
                 sourceconf.set_digipot(code);
@@ -1470,6 +1470,21 @@ mod sourceconftests {
         assert_eq!(c.get_digipot(), 0x3ff);
 
     }
+
+    #[test]
+    fn test_sourceconf_for_current() {
+        // Sweep 1 nA to 10 mA, 10 points per decade, both polarities;
+        // the voltage drop across the series resistor must never exceed
+        // 1.0 V
+        for exp in -90..=-20 {
+            let current = 10.0f32.powf((exp as f32)/10.0);
+            for c in &[current, -current] {
+                let (_, vdrop) = SourceConf::for_current(*c).unwrap();
+                assert!(vdrop > 0.0 && vdrop <= 1.0,
+                    "vdrop {} V out of range for {} A", vdrop, c);
+            }
+        }
+    }
 }
 
 